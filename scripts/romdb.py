@@ -0,0 +1,94 @@
+#!/usr/bin/env python3
+"""Regenerates src/romdb.toml from the CHIP-8 community program database.
+
+    scripts/romdb.py [programs.json] > src/romdb.toml
+
+Without an argument, programs.json is downloaded from the chip-8-database
+repository. The entries in scripts/romdb-local.toml are appended and win over
+upstream entries for the same ROM.
+"""
+
+import json
+import re
+import sys
+import urllib.request
+from pathlib import Path
+
+UPSTREAM = (
+    "https://raw.githubusercontent.com/chip-8/chip-8-database/master/database/programs.json"
+)
+LOCAL = Path(__file__).with_name("romdb-local.toml")
+
+# Upstream platform ids to --platform values. Platforms the emulator has no
+# profile for are left out, so the user's settings apply.
+PLATFORMS = {
+    "originalChip8": "chip8",
+    "hybridVIP": "chip8",
+    "modernChip8": "chip8",
+    "chip48": "schip",
+    "superchip1": "schip",
+    "superchip": "schip",
+    "xochip": "xochip",
+}
+
+# Upstream quirk names to config keys.
+QUIRKS = {
+    "vblank": "display_wait",
+    "wrap": "wrap_sprites",
+}
+
+
+def string(value):
+    """A TOML basic string the emulator's parser reads back unchanged."""
+    value = "".join(c for c in value if c >= " ")
+    return json.dumps(value, ensure_ascii=False)
+
+
+def entry(sha1, program, rom):
+    lines = [f'[rom."{sha1}"]', f"title = {string(program['title'])}"]
+    if program.get("authors"):
+        lines.append(f"author = {string(', '.join(program['authors']))}")
+    year = re.match(r"\d{4}", str(program.get("release", "")))
+    if year:
+        lines.append(f"year = {year.group()}")
+    platform = next(iter(rom.get("platforms", [])), None)
+    if platform in PLATFORMS:
+        lines.append(f'platform = "{PLATFORMS[platform]}"')
+        quirks = rom.get("quirkyPlatforms", {}).get(platform, {})
+        for name, key in QUIRKS.items():
+            if isinstance(quirks.get(name), bool):
+                lines.append(f"{key} = {str(quirks[name]).lower()}")
+    if isinstance(rom.get("tickrate"), int):
+        lines.append(f"ipf = {rom['tickrate']}")
+    return "\n".join(lines)
+
+
+def main():
+    if len(sys.argv) > 1:
+        programs = json.loads(Path(sys.argv[1]).read_text())
+    else:
+        with urllib.request.urlopen(UPSTREAM) as response:
+            programs = json.load(response)
+    local = LOCAL.read_text()
+    overridden = set(re.findall(r'^\[rom\."([0-9a-f]{40})"\]', local, re.M))
+    entries = {
+        sha1: entry(sha1, program, rom)
+        for program in programs
+        for sha1, rom in program.get("roms", {}).items()
+        if sha1 not in overridden
+    }
+    print("# Generated by scripts/romdb.py from the CHIP-8 community program")
+    print("# database, keyed by the SHA-1 of the ROM file, followed by the local")
+    print("# entries in scripts/romdb-local.toml. Regenerate rather than edit.")
+    print("# Besides the descriptive fields, any key accepted in the config file")
+    print("# can be given and is applied before the user's own config.")
+    print(f"# Upstream entries: {len(entries)}")
+    for sha1 in sorted(entries):
+        print()
+        print(entries[sha1])
+    print()
+    print(local, end="")
+
+
+if __name__ == "__main__":
+    main()
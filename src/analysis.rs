@@ -0,0 +1,504 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{disasm::instruction_line, memory::Memory, quirks::Platform};
+
+/// Instruction set extensions beyond the original CHIP-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extension {
+    SuperChip,
+    XoChip,
+}
+
+impl std::fmt::Display for Extension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Extension::SuperChip => "SCHIP",
+            Extension::XoChip => "XO-CHIP",
+        })
+    }
+}
+
+/// An extension opcode, by what it does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExtensionOp {
+    ScrollDown,
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    Lores,
+    Hires,
+    BigSprite,
+    BigFont,
+    SaveFlags,
+    LoadFlags,
+    ScrollUp,
+    SaveRange,
+    LoadRange,
+    LongIndex,
+    Plane,
+    Audio,
+    Pitch,
+}
+
+impl ExtensionOp {
+    /// The extension an opcode belongs to and what it does.
+    fn decode(opcode: u16) -> Option<Self> {
+        let (x, n, nn) = ((opcode >> 8) & 0xF, opcode & 0xF, opcode & 0xFF);
+        match (opcode >> 12, x, nn) {
+            (0x0, 0x0, 0xC0..=0xCF) => Some(Self::ScrollDown),
+            (0x0, 0x0, 0xFB) => Some(Self::ScrollRight),
+            (0x0, 0x0, 0xFC) => Some(Self::ScrollLeft),
+            (0x0, 0x0, 0xFD) => Some(Self::Exit),
+            (0x0, 0x0, 0xFE) => Some(Self::Lores),
+            (0x0, 0x0, 0xFF) => Some(Self::Hires),
+            (0xD, _, _) if n == 0 => Some(Self::BigSprite),
+            (0xF, _, 0x30) => Some(Self::BigFont),
+            (0xF, _, 0x75) => Some(Self::SaveFlags),
+            (0xF, _, 0x85) => Some(Self::LoadFlags),
+            (0x0, 0x0, 0xD0..=0xDF) => Some(Self::ScrollUp),
+            (0x5, _, _) if n == 2 => Some(Self::SaveRange),
+            (0x5, _, _) if n == 3 => Some(Self::LoadRange),
+            (0xF, 0x0, 0x00) => Some(Self::LongIndex),
+            (0xF, _, 0x01) => Some(Self::Plane),
+            (0xF, 0x0, 0x02) => Some(Self::Audio),
+            (0xF, _, 0x3A) => Some(Self::Pitch),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> Extension {
+        match self {
+            Self::ScrollDown
+            | Self::ScrollRight
+            | Self::ScrollLeft
+            | Self::Exit
+            | Self::Lores
+            | Self::Hires
+            | Self::BigSprite
+            | Self::BigFont
+            | Self::SaveFlags
+            | Self::LoadFlags => Extension::SuperChip,
+            Self::ScrollUp
+            | Self::SaveRange
+            | Self::LoadRange
+            | Self::LongIndex
+            | Self::Plane
+            | Self::Audio
+            | Self::Pitch => Extension::XoChip,
+        }
+    }
+
+    /// Whether the core executes it. The others stop it with an unknown
+    /// opcode error, or for `DXY0` draw nothing.
+    pub fn implemented(&self) -> bool {
+        matches!(self, Self::Plane | Self::Audio | Self::Pitch)
+    }
+}
+
+impl std::fmt::Display for ExtensionOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::ScrollDown => "00CN scroll down",
+            Self::ScrollRight => "00FB scroll right",
+            Self::ScrollLeft => "00FC scroll left",
+            Self::Exit => "00FD exit",
+            Self::Lores => "00FE lores",
+            Self::Hires => "00FF hires",
+            Self::BigSprite => "DXY0 16x16 sprite",
+            Self::BigFont => "FX30 big font",
+            Self::SaveFlags => "FX75 save flags",
+            Self::LoadFlags => "FX85 load flags",
+            Self::ScrollUp => "00DN scroll up",
+            Self::SaveRange => "5XY2 save range",
+            Self::LoadRange => "5XY3 load range",
+            Self::LongIndex => "F000 long index",
+            Self::Plane => "FN01 plane",
+            Self::Audio => "F002 audio",
+            Self::Pitch => "FX3A pitch",
+        })
+    }
+}
+
+/// How control gets from one instruction to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Next,
+    Jump,
+    /// The taken side of a conditional skip.
+    Skip,
+    Call,
+    /// A `BNNN` target seen at runtime.
+    Indirect,
+}
+
+/// Static analysis of a program loaded at `Memory::PROGRAM_START`, found by
+/// following control flow from the entry point.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// Address and length in bytes of every reachable instruction.
+    pub instructions: BTreeMap<u16, u16>,
+    /// Control flow out of each reachable instruction. `00EE` and `00FD`
+    /// have none.
+    pub edges: BTreeMap<u16, Vec<(u16, EdgeKind)>>,
+    /// Extension opcodes in reachable code, with the first address each
+    /// was found at.
+    pub extensions: BTreeMap<ExtensionOp, u16>,
+    /// `0NNN` calls into machine code: address and target.
+    pub machine_calls: Vec<(u16, u16)>,
+    /// `BNNN` jumps whose target depends on `V0`, with the targets seen in
+    /// coverage data. Flow past one without coverage can't be followed.
+    pub indirect_jumps: BTreeMap<u16, BTreeSet<u16>>,
+}
+
+impl Analysis {
+    pub fn new(program: &[u8]) -> Self {
+        Self::with_coverage(program, &[])
+    }
+
+    /// Like `new`, but also follows the `BNNN` targets observed in
+    /// `executed`, a runtime sequence of instruction addresses.
+    pub fn with_coverage(program: &[u8], executed: &[u16]) -> Self {
+        let mut observed = BTreeMap::<u16, BTreeSet<u16>>::new();
+        for pair in executed.windows(2) {
+            if word(program, pair[0]).is_some_and(|opcode| opcode >> 12 == 0xB) {
+                observed.entry(pair[0]).or_default().insert(pair[1]);
+            }
+        }
+        Self::with_indirect_targets(program, observed)
+    }
+
+    /// Like `new`, but follows the given targets of `BNNN` jumps by address.
+    pub fn with_indirect_targets(
+        program: &[u8],
+        mut observed: BTreeMap<u16, BTreeSet<u16>>,
+    ) -> Self {
+        let mut analysis = Self::default();
+        let word = |addr: u16| word(program, addr);
+        let mut pending = vec![Memory::PROGRAM_START];
+        while let Some(addr) = pending.pop() {
+            if analysis.instructions.contains_key(&addr) {
+                continue;
+            }
+            // A zero word is padding or inline data far more often than a
+            // machine code call to address 0
+            let Some(opcode) = word(addr).filter(|opcode| *opcode != 0) else {
+                continue;
+            };
+            let long = opcode == 0xF000;
+            let len = if long { 4 } else { 2 };
+            analysis.instructions.insert(addr, len);
+            if let Some(op) = ExtensionOp::decode(opcode) {
+                analysis.extensions.entry(op).or_insert(addr);
+            }
+            let next = addr + len;
+            // A skip jumps over a whole instruction, which may be a long one
+            let skip = next + if word(next) == Some(0xF000) { 4 } else { 2 };
+            let nnn = opcode & 0x0FFF;
+            let edges = match opcode >> 12 {
+                0x0 => match opcode {
+                    0x00EE | 0x00FD => vec![],
+                    0x00E0 | 0x00FB..=0x00FF => vec![(next, EdgeKind::Next)],
+                    _ if opcode & 0xFFF0 == 0x00C0 || opcode & 0xFFF0 == 0x00D0 => {
+                        vec![(next, EdgeKind::Next)]
+                    }
+                    _ => {
+                        analysis.machine_calls.push((addr, nnn));
+                        vec![(next, EdgeKind::Next)]
+                    }
+                },
+                0x1 => vec![(nnn, EdgeKind::Jump)],
+                0x2 => vec![(nnn, EdgeKind::Call), (next, EdgeKind::Next)],
+                0x3 | 0x4 | 0x5 | 0x9 => vec![(next, EdgeKind::Next), (skip, EdgeKind::Skip)],
+                0xB => {
+                    let targets = observed.remove(&addr).unwrap_or_default();
+                    let edges = targets.iter().map(|t| (*t, EdgeKind::Indirect)).collect();
+                    analysis.indirect_jumps.insert(addr, targets);
+                    edges
+                }
+                0xE if matches!(opcode & 0xFF, 0x9E | 0xA1) => {
+                    vec![(next, EdgeKind::Next), (skip, EdgeKind::Skip)]
+                }
+                _ => vec![(next, EdgeKind::Next)],
+            };
+            pending.extend(edges.iter().map(|(to, _)| *to));
+            analysis.edges.insert(addr, edges);
+        }
+        analysis
+    }
+
+    /// Bytes covered by reachable instructions.
+    pub fn code_bytes(&self) -> usize {
+        self.instructions.values().map(|len| *len as usize).sum()
+    }
+
+    /// The oldest platform supporting every extension opcode found.
+    pub fn suggested_platform(&self) -> Platform {
+        match self.extensions.keys().map(ExtensionOp::extension).max() {
+            Some(Extension::XoChip) => Platform::XoChip,
+            Some(Extension::SuperChip) => Platform::SuperChip,
+            None => Platform::Chip8,
+        }
+    }
+
+    /// Extension opcodes found that the core doesn't implement.
+    pub fn unsupported(&self) -> impl Iterator<Item = ExtensionOp> + '_ {
+        self.extensions
+            .keys()
+            .copied()
+            .filter(|op| !op.implemented())
+    }
+
+    /// Basic blocks by start address, each a run of instructions entered only
+    /// at the top and left only at the bottom. Calls don't end a block.
+    pub fn blocks(&self) -> BTreeMap<u16, Vec<u16>> {
+        let mut leaders = BTreeSet::from([Memory::PROGRAM_START]);
+        for (addr, edges) in &self.edges {
+            let branches = self.fallthrough(*addr).is_none();
+            leaders.extend(
+                edges
+                    .iter()
+                    .filter(|(_, kind)| branches || *kind != EdgeKind::Next)
+                    .map(|(to, _)| *to),
+            );
+        }
+        leaders
+            .iter()
+            .filter(|leader| self.instructions.contains_key(leader))
+            .map(|&leader| {
+                let mut block = vec![leader];
+                while let Some(next) = self.fallthrough(*block.last().unwrap()) {
+                    if leaders.contains(&next) || !self.instructions.contains_key(&next) {
+                        break;
+                    }
+                    block.push(next);
+                }
+                (leader, block)
+            })
+            .collect()
+    }
+
+    /// The only instruction control reaches from `addr` within the current
+    /// subroutine, if it runs straight on.
+    fn fallthrough(&self, addr: u16) -> Option<u16> {
+        match self.edges.get(&addr)?.as_slice() {
+            [(next, EdgeKind::Next)] | [(_, EdgeKind::Call), (next, EdgeKind::Next)] => Some(*next),
+            _ => None,
+        }
+    }
+
+    /// Subroutine entry points: the program entry and every `2NNN` target,
+    /// each with the subroutines it calls.
+    pub fn call_graph(&self) -> BTreeMap<u16, BTreeSet<u16>> {
+        let entries = std::iter::once(Memory::PROGRAM_START)
+            .chain(self.edges.values().flatten().filter_map(|(to, kind)| {
+                (*kind == EdgeKind::Call && self.instructions.contains_key(to)).then_some(*to)
+            }))
+            .collect::<BTreeSet<_>>();
+        entries
+            .iter()
+            .map(|&entry| {
+                let mut calls = BTreeSet::new();
+                let mut seen = BTreeSet::new();
+                let mut pending = vec![entry];
+                while let Some(addr) = pending.pop() {
+                    if !seen.insert(addr) {
+                        continue;
+                    }
+                    for (to, kind) in self.edges.get(&addr).into_iter().flatten() {
+                        match kind {
+                            EdgeKind::Call => {
+                                calls.insert(*to);
+                            }
+                            _ => pending.push(*to),
+                        }
+                    }
+                }
+                (entry, calls)
+            })
+            .collect()
+    }
+
+    /// The control-flow graph as Graphviz DOT, one node per basic block.
+    pub fn cfg_dot(&self, program: &[u8]) -> String {
+        let mut out =
+            String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for (start, block) in self.blocks() {
+            let mut label = block
+                .iter()
+                .map(|addr| format!("{}\\l", instruction_line(program, *addr)))
+                .collect::<String>();
+            let last = block.last().copied().unwrap_or(start);
+            let unresolved = self.indirect_jumps.get(&last).is_some_and(|t| t.is_empty());
+            if unresolved {
+                label.push_str("(indirect jump, no coverage)\\l");
+            }
+            let style = if unresolved { ", color=red" } else { "" };
+            out.push_str(&format!(
+                "    \"0x{start:03X}\" [label=\"{}\"{style}];\n",
+                label.replace('"', "\\\"")
+            ));
+            for (to, kind) in self.edges.get(&last).into_iter().flatten() {
+                let attributes = match kind {
+                    EdgeKind::Next => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                    EdgeKind::Skip => " [label=\"skip\", style=dashed]",
+                    EdgeKind::Indirect => " [label=\"indirect\", style=dotted, color=red]",
+                    EdgeKind::Call => continue,
+                };
+                out.push_str(&format!(
+                    "    \"0x{start:03X}\" -> \"0x{to:03X}\"{attributes};\n"
+                ));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// The call graph as Graphviz DOT, one node per subroutine.
+    pub fn call_graph_dot(&self) -> String {
+        let mut out =
+            String::from("digraph calls {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for (entry, calls) in self.call_graph() {
+            let name = subroutine_name(entry);
+            out.push_str(&format!("    \"0x{entry:03X}\" [label=\"{name}\"];\n"));
+            for to in calls {
+                out.push_str(&format!("    \"0x{entry:03X}\" -> \"0x{to:03X}\";\n"));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// The big-endian word at `addr` in a program loaded at `Memory::PROGRAM_START`.
+fn word(program: &[u8], addr: u16) -> Option<u16> {
+    let offset = addr.checked_sub(Memory::PROGRAM_START)? as usize;
+    Some(u16::from_be_bytes([
+        *program.get(offset)?,
+        *program.get(offset + 1)?,
+    ]))
+}
+
+/// The name a subroutine gets in reports and call graphs.
+pub fn subroutine_name(entry: u16) -> String {
+    match entry {
+        Memory::PROGRAM_START => "main".to_string(),
+        _ => format!("sub_{entry:03X}"),
+    }
+}
+
+/// Addresses from a `--trace` log, in execution order.
+pub fn parse_trace(text: &str) -> Vec<u16> {
+    text.lines()
+        .filter_map(|line| line.split_whitespace().next()?.strip_prefix("0x"))
+        .filter_map(|addr| u16::from_str_radix(addr, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reachability() {
+        let program = [
+            0x22, 0x08, // 0x200: CALL 0x208
+            0x3A, 0x01, // 0x202: SE VA, 0x01
+            0x12, 0x02, // 0x204: JP 0x202
+            0x12, 0x06, // 0x206: JP 0x206 (skip target)
+            0x00, 0xEE, // 0x208: RET
+            0xFF, 0xFF, // 0x20A: data
+        ];
+        let analysis = Analysis::new(&program);
+        assert_eq!(
+            analysis.instructions.keys().copied().collect::<Vec<_>>(),
+            [0x200, 0x202, 0x204, 0x206, 0x208]
+        );
+        assert_eq!(analysis.code_bytes(), 10);
+        assert_eq!(analysis.suggested_platform(), Platform::Chip8);
+    }
+
+    #[test]
+    fn test_extensions() {
+        let program = [
+            0x00, 0xFF, // 0x200: hires
+            0x03, 0x00, // 0x202: SYS 0x300
+            0x40, 0x00, // 0x204: SNE V0, 0
+            0xF0, 0x00, 0x12, 0x34, // 0x206: long index, skipped as one
+            0xB2, 0x00, // 0x20A: JP V0, 0x200
+        ];
+        let analysis = Analysis::new(&program);
+        assert_eq!(analysis.machine_calls, [(0x202, 0x300)]);
+        assert_eq!(analysis.indirect_jumps.keys().collect::<Vec<_>>(), [&0x20A]);
+        assert!(!analysis.instructions.contains_key(&0x208));
+        assert_eq!(
+            analysis.extensions.keys().copied().collect::<Vec<_>>(),
+            [ExtensionOp::Hires, ExtensionOp::LongIndex]
+        );
+        assert_eq!(analysis.suggested_platform(), Platform::XoChip);
+        assert_eq!(
+            analysis.unsupported().collect::<Vec<_>>(),
+            [ExtensionOp::Hires, ExtensionOp::LongIndex]
+        );
+        let audio = Analysis::new(&[0xF0, 0x02, 0xF1, 0x3A, 0x12, 0x04]);
+        assert_eq!(audio.extensions.len(), 2);
+        assert_eq!(audio.unsupported().count(), 0);
+    }
+
+    #[test]
+    fn test_blocks_and_call_graph() {
+        let program = [
+            0x22, 0x08, // 0x200: CALL 0x208
+            0x3A, 0x01, // 0x202: SE VA, 0x01
+            0x12, 0x02, // 0x204: JP 0x202
+            0x12, 0x06, // 0x206: JP 0x206
+            0x22, 0x0C, // 0x208: CALL 0x20C
+            0x00, 0xEE, // 0x20A: RET
+            0x00, 0xEE, // 0x20C: RET
+        ];
+        let analysis = Analysis::new(&program);
+        assert_eq!(
+            analysis.blocks().into_iter().collect::<Vec<_>>(),
+            [
+                (0x200, vec![0x200]),
+                (0x202, vec![0x202]),
+                (0x204, vec![0x204]),
+                (0x206, vec![0x206]),
+                (0x208, vec![0x208, 0x20A]),
+                (0x20C, vec![0x20C]),
+            ]
+        );
+        let calls = analysis.call_graph();
+        assert_eq!(calls[&0x200], BTreeSet::from([0x208]));
+        assert_eq!(calls[&0x208], BTreeSet::from([0x20C]));
+        assert!(calls[&0x20C].is_empty());
+        assert!(
+            analysis
+                .call_graph_dot()
+                .contains("\"0x200\" -> \"0x208\";")
+        );
+        let cfg = analysis.cfg_dot(&program);
+        assert!(cfg.contains("\"0x202\" -> \"0x206\" [label=\"skip\", style=dashed];"));
+        assert!(cfg.contains("0x208  220C  CALL 0x20C\\l0x20A  00EE  RET\\l"));
+    }
+
+    #[test]
+    fn test_coverage_resolves_indirect_jumps() {
+        let program = [
+            0x60, 0x02, // 0x200: LD V0, 0x02
+            0xB2, 0x04, // 0x202: JP V0, 0x204
+            0xFF, 0xFF, // 0x204: data
+            0x12, 0x06, // 0x206: JP 0x206
+        ];
+        let unresolved = Analysis::new(&program);
+        assert!(unresolved.indirect_jumps[&0x202].is_empty());
+        assert!(!unresolved.instructions.contains_key(&0x206));
+        assert!(unresolved.cfg_dot(&program).contains("color=red"));
+
+        let trace = "0x200  6002  LD V0, 0x02\n0x202  B204  JP V0, 0x204\n0x206  1206  JP 0x206\n";
+        let resolved = Analysis::with_coverage(&program, &parse_trace(trace));
+        assert_eq!(resolved.indirect_jumps[&0x202], BTreeSet::from([0x206]));
+        assert!(resolved.instructions.contains_key(&0x206));
+        assert!(!resolved.instructions.contains_key(&0x204));
+    }
+}
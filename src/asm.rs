@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::memory::Memory;
+
+/// Assembles the mnemonics produced by `disasm` back into a ROM, skipping the
+/// listing's address and raw word columns. Supports `label:` definitions, `;`
+/// comments and `DB`/`DW` data directives; numbers are decimal or `0x` hex and
+/// anywhere an address is expected a label works.
+pub fn assemble(source: &str) -> Result<Vec<u8>> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.split(';').next().unwrap_or("").trim()))
+        .map(|(number, line)| (number, strip_listing_columns(line)))
+        .filter(|(_, line)| !line.is_empty())
+        .collect::<Vec<_>>();
+
+    // First pass: label addresses
+    let mut labels = HashMap::new();
+    let mut addr = Memory::PROGRAM_START;
+    for &(_, line) in &lines {
+        let (label, rest) = split_label(line);
+        if let Some(label) = label {
+            labels.insert(label.to_ascii_uppercase(), addr);
+        }
+        if let Some(rest) = rest {
+            let (mnemonic, operands) = split_instruction(rest);
+            addr += match mnemonic.as_str() {
+                "DB" => operands.len() as u16,
+                "DW" => operands.len() as u16 * 2,
+                _ => 2,
+            };
+        }
+    }
+
+    // Second pass: encode
+    let mut rom = Vec::new();
+    for (number, line) in lines {
+        let (_, Some(rest)) = split_label(line) else {
+            continue;
+        };
+        let (mnemonic, operands) = split_instruction(rest);
+        let at_line = |e: Error| Error::Unknown(format!("line {number}: {e}"));
+        match mnemonic.as_str() {
+            "DB" => {
+                for operand in &operands {
+                    rom.push(value(operand, &labels, 0xFF).map_err(at_line)? as u8);
+                }
+            }
+            "DW" => {
+                for operand in &operands {
+                    rom.extend(
+                        value(operand, &labels, 0xFFFF)
+                            .map_err(at_line)?
+                            .to_be_bytes(),
+                    );
+                }
+            }
+            _ => {
+                let opcode = encode(&mnemonic, &operands, &labels).map_err(at_line)?;
+                rom.extend(opcode.to_be_bytes());
+            }
+        }
+    }
+    Ok(rom)
+}
+
+fn split_label(line: &str) -> (Option<&str>, Option<&str>) {
+    match line.split_once(':') {
+        Some((label, rest)) if !label.contains(char::is_whitespace) => {
+            let rest = rest.trim();
+            (Some(label), (!rest.is_empty()).then_some(rest))
+        }
+        _ => (None, Some(line)),
+    }
+}
+
+fn split_instruction(line: &str) -> (String, Vec<String>) {
+    let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let operands = operands
+        .split(',')
+        .map(|op| op.trim().to_ascii_uppercase())
+        .filter(|op| !op.is_empty())
+        .collect();
+    (mnemonic.to_ascii_uppercase(), operands)
+}
+
+fn register(operand: &str) -> Option<u16> {
+    let digit = operand.strip_prefix('V')?;
+    (digit.len() == 1)
+        .then(|| u16::from_str_radix(digit, 16).ok())
+        .flatten()
+}
+
+fn value(operand: &str, labels: &HashMap<String, u16>, max: u16) -> Result<u16> {
+    let parsed = match operand.strip_prefix("0X") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => operand
+            .parse::<u16>()
+            .ok()
+            .or_else(|| labels.get(operand).copied()),
+    };
+    match parsed {
+        Some(v) if v <= max => Ok(v),
+        Some(v) => Err(Error::Unknown(format!("value out of range: {v:#X}"))),
+        None => Err(Error::Unknown(format!("bad operand: {operand}"))),
+    }
+}
+
+fn encode(mnemonic: &str, operands: &[String], labels: &HashMap<String, u16>) -> Result<u16> {
+    let ops = operands.iter().map(String::as_str).collect::<Vec<_>>();
+    let vx = |i: usize| {
+        ops.get(i)
+            .and_then(|op| register(op))
+            .ok_or_else(|| Error::Unknown(format!("expected register in {mnemonic}")))
+    };
+    let addr = |i: usize| value(ops.get(i).copied().unwrap_or(""), labels, 0xFFF);
+    let byte = |i: usize| value(ops.get(i).copied().unwrap_or(""), labels, 0xFF);
+    let xy = |code: u16, n: u16| Ok(code | vx(0)? << 8 | vx(1)? << 4 | n);
+    let xnn = |code: u16| Ok(code | vx(0)? << 8 | byte(1)?);
+    let fx = |nn: u16, i: usize| Ok(0xF000 | vx(i)? << 8 | nn);
+    let second_is_register = ops.get(1).is_some_and(|op| register(op).is_some());
+    match (mnemonic, ops.as_slice()) {
+        ("CLS", []) => Ok(0x00E0),
+        ("RET", []) => Ok(0x00EE),
+        ("SYS", [_]) => Ok(addr(0)?),
+        ("JP", ["V0", _]) => Ok(0xB000 | addr(1)?),
+        ("JP", [_]) => Ok(0x1000 | addr(0)?),
+        ("CALL", [_]) => Ok(0x2000 | addr(0)?),
+        ("SE", [_, _]) if second_is_register => xy(0x5000, 0),
+        ("SE", [_, _]) => xnn(0x3000),
+        ("SNE", [_, _]) if second_is_register => xy(0x9000, 0),
+        ("SNE", [_, _]) => xnn(0x4000),
+        ("LD", ["I", _]) => Ok(0xA000 | addr(1)?),
+        ("LD", ["DT", _]) => fx(0x15, 1),
+        ("LD", ["ST", _]) => fx(0x18, 1),
+        ("LD", ["F", _]) => fx(0x29, 1),
+        ("LD", ["B", _]) => fx(0x33, 1),
+        ("LD", ["[I]", _]) => fx(0x55, 1),
+        ("LD", [_, "DT"]) => fx(0x07, 0),
+        ("LD", [_, "K"]) => fx(0x0A, 0),
+        ("LD", [_, "[I]"]) => fx(0x65, 0),
+        ("LD", [_, _]) if second_is_register => xy(0x8000, 0x0),
+        ("LD", [_, _]) => xnn(0x6000),
+        ("ADD", ["I", _]) => fx(0x1E, 1),
+        ("ADD", [_, _]) if second_is_register => xy(0x8000, 0x4),
+        ("ADD", [_, _]) => xnn(0x7000),
+        ("OR", [_, _]) => xy(0x8000, 0x1),
+        ("AND", [_, _]) => xy(0x8000, 0x2),
+        ("XOR", [_, _]) => xy(0x8000, 0x3),
+        ("SUB", [_, _]) => xy(0x8000, 0x5),
+        ("SHR", [_, _]) => xy(0x8000, 0x6),
+        ("SUBN", [_, _]) => xy(0x8000, 0x7),
+        ("SHL", [_, _]) => xy(0x8000, 0xE),
+        ("RND", [_, _]) => xnn(0xC000),
+        ("DRW", [_, _, n]) => xy(0xD000, value(n, labels, 0xF)?),
+        ("SKP", [_]) => Ok(0xE09E | vx(0)? << 8),
+        ("SKNP", [_]) => Ok(0xE0A1 | vx(0)? << 8),
+        ("AUDIO", []) => Ok(0xF002),
+        ("PITCH", [_]) => fx(0x3A, 0),
+        _ => Err(Error::Unknown(format!(
+            "unknown instruction: {mnemonic} {}",
+            operands.join(", ")
+        ))),
+    }
+}
+
+/// `line` without a leading `0x200  00E0` address and raw word, as `disasm`
+/// writes them. Other lines are returned as they are.
+fn strip_listing_columns(line: &str) -> &str {
+    let hex = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit());
+    let Some(rest) = line.strip_prefix("0x") else {
+        return line;
+    };
+    let (addr, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let (word, rest) = rest
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest, ""));
+    match hex(addr) && hex(word) && word.len() <= 4 {
+        true => rest.trim_start(),
+        false => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::Analysis, disasm::disassemble_rom};
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            start:  CLS          ; clear
+                    LD V0, 0x10
+                    LD I, sprite
+            loop:   DRW V0, V1, 5
+                    SKP VA
+                    JP loop
+            sprite: DB 0xF0, 0x90
+                    DW 0x1234
+        ";
+        let rom = assemble(source).unwrap();
+        assert_eq!(
+            rom,
+            [
+                0x00, 0xE0, 0x60, 0x10, 0xA2, 0x0C, 0xD0, 0x15, 0xEA, 0x9E, 0x12, 0x06, 0xF0, 0x90,
+                0x12, 0x34
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassembly_roundtrip() {
+        for path in ["roms/ibm.ch8", "roms/br8kout.ch8", "tests/5-quirks.ch8"] {
+            let rom = std::fs::read(path).unwrap();
+            let source = disassemble_rom(&rom, &Analysis::new(&rom)).join("\n");
+            assert_eq!(assemble(&source).unwrap(), rom, "{path}");
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+use crate::error::{Error, Result};
+
+pub const SAMPLE_RATE: u32 = 44_100;
+const FRAME_RATE: u32 = 60;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Sine,
+    Triangle,
+    Sawtooth,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ToneConfig {
+    pub frequency: f32,
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for ToneConfig {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::default(),
+        }
+    }
+}
+
+/// Snapshot of the audio registers taken at the end of a 60 Hz frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFrame {
+    pub playing: bool,
+    /// XO-CHIP 1-bit sample pattern, once a program has loaded one with `F002`.
+    pub pattern: Option<[u8; 16]>,
+    pub pitch: u8,
+}
+
+/// Receives the audio state once per 60 Hz frame. A real-time backend only has
+/// to implement this to be driven by the emulator.
+pub trait AudioSink {
+    fn frame(&mut self, frame: AudioFrame) -> Result<()>;
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn frame(&mut self, _frame: AudioFrame) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Rings the terminal bell whenever the tone starts.
+#[derive(Debug, Default)]
+pub struct BellSink {
+    playing: bool,
+}
+
+impl AudioSink for BellSink {
+    fn frame(&mut self, frame: AudioFrame) -> Result<()> {
+        if frame.playing && !self.playing {
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x07")?;
+            stdout.flush()?;
+        }
+        self.playing = frame.playing;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ToneGenerator {
+    config: ToneConfig,
+    phase: f32,
+}
+
+impl ToneGenerator {
+    pub fn new(config: ToneConfig) -> Self {
+        Self { config, phase: 0.0 }
+    }
+
+    /// Appends one frame worth of samples, silence when not playing.
+    pub fn frame(&mut self, playing: bool, out: &mut Vec<i16>) {
+        let step = self.config.frequency / SAMPLE_RATE as f32;
+        for _ in 0..SAMPLE_RATE / FRAME_RATE {
+            if !playing {
+                out.push(0);
+                continue;
+            }
+            let value = match self.config.waveform {
+                Waveform::Square => {
+                    if self.phase < 0.5 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                Waveform::Sine => (self.phase * std::f32::consts::TAU).sin(),
+                Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+                Waveform::Sawtooth => 2.0 * self.phase - 1.0,
+            };
+            out.push((value * self.config.volume.clamp(0.0, 1.0) * i16::MAX as f32) as i16);
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
+
+/// Plays back an XO-CHIP 128-bit pattern as 1-bit audio.
+#[derive(Debug)]
+pub struct PatternGenerator {
+    volume: f32,
+    /// Position in the pattern, in bits.
+    position: f64,
+}
+
+impl PatternGenerator {
+    const OVERSAMPLE: u32 = 8;
+
+    pub fn new(volume: f32) -> Self {
+        Self {
+            volume,
+            position: 0.0,
+        }
+    }
+
+    /// Pattern bits played per second for a given pitch register value.
+    pub fn playback_rate(pitch: u8) -> f64 {
+        4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0)
+    }
+
+    /// Appends one frame worth of samples at the host rate. Each host sample is
+    /// the average of several pattern reads across its interval, which keeps
+    /// high pitches from aliasing too badly.
+    pub fn frame(&mut self, playing: bool, pattern: &[u8; 16], pitch: u8, out: &mut Vec<i16>) {
+        let step = Self::playback_rate(pitch) / SAMPLE_RATE as f64;
+        let sub_step = step / Self::OVERSAMPLE as f64;
+        for _ in 0..SAMPLE_RATE / FRAME_RATE {
+            if !playing {
+                out.push(0);
+                continue;
+            }
+            let mut sum = 0.0;
+            for _ in 0..Self::OVERSAMPLE {
+                let bit = self.position as usize % 128;
+                sum += if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                    1.0
+                } else {
+                    -1.0
+                };
+                self.position = (self.position + sub_step) % 128.0;
+            }
+            let value = sum / Self::OVERSAMPLE as f32;
+            out.push((value * self.volume.clamp(0.0, 1.0) * i16::MAX as f32) as i16);
+        }
+    }
+}
+
+/// Streams the tone into a mono 16-bit PCM WAV file. The header's sizes are
+/// left at 0 until `finish` goes back and fills them in.
+#[derive(Debug)]
+pub struct WavSink<W: Write + Seek> {
+    out: W,
+    tone: ToneGenerator,
+    pattern: PatternGenerator,
+    /// The current frame's samples.
+    samples: Vec<i16>,
+    data_len: u32,
+}
+
+impl<W: Write + Seek> WavSink<W> {
+    pub fn new(mut out: W, config: ToneConfig) -> Result<Self> {
+        write_header(&mut out, 0)?;
+        Ok(Self {
+            out,
+            tone: ToneGenerator::new(config),
+            pattern: PatternGenerator::new(config.volume),
+            samples: Vec::new(),
+            data_len: 0,
+        })
+    }
+}
+
+impl<W: Write + Seek> AudioSink for WavSink<W> {
+    fn frame(&mut self, frame: AudioFrame) -> Result<()> {
+        match frame.pattern {
+            Some(pattern) => {
+                self.pattern
+                    .frame(frame.playing, &pattern, frame.pitch, &mut self.samples)
+            }
+            None => self.tone.frame(frame.playing, &mut self.samples),
+        }
+        let bytes = self
+            .samples
+            .drain(..)
+            .flat_map(i16::to_le_bytes)
+            .collect::<Vec<_>>();
+        self.out.write_all(&bytes)?;
+        self.data_len += bytes.len() as u32;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.out, self.data_len)?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// The 44 byte header of a WAV file holding `data_len` bytes of samples.
+fn write_header<W: Write>(out: &mut W, data_len: u32) -> Result<()> {
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?; // PCM
+    out.write_all(&1u16.to_le_bytes())?; // mono
+    out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    out.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+    Ok(())
+}
+
+impl std::str::FromStr for Waveform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            "sawtooth" | "saw" => Ok(Waveform::Sawtooth),
+            _ => Err(Error::Unknown(format!("unknown waveform: {s}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_sink() {
+        let mut out = io::Cursor::new(Vec::new());
+        let mut sink = WavSink::new(&mut out, ToneConfig::default()).unwrap();
+        sink.frame(AudioFrame::default()).unwrap();
+        sink.frame(AudioFrame {
+            playing: true,
+            ..Default::default()
+        })
+        .unwrap();
+        sink.finish().unwrap();
+        let out = out.into_inner();
+        let frame = (SAMPLE_RATE / FRAME_RATE) as usize;
+        assert_eq!(&out[0..4], b"RIFF");
+        assert_eq!(out.len(), 44 + frame * 2 * 2);
+        let size = |at: usize| u32::from_le_bytes(out[at..at + 4].try_into().unwrap()) as usize;
+        assert_eq!(size(4), out.len() - 8);
+        assert_eq!(size(40), frame * 2 * 2);
+        let samples = &out[44..];
+        assert!(samples[..frame * 2].iter().all(|b| *b == 0));
+        assert!(samples[frame * 2..].iter().any(|b| *b != 0));
+    }
+
+    #[test]
+    fn test_pattern_playback() {
+        // 4 bits high, 4 bits low at 4000 bits/s is a 500 Hz square wave
+        let pattern = [0xF0; 16];
+        let mut out = io::Cursor::new(Vec::new());
+        let mut sink = WavSink::new(&mut out, ToneConfig::default()).unwrap();
+        for _ in 0..FRAME_RATE {
+            sink.frame(AudioFrame {
+                playing: true,
+                pattern: Some(pattern),
+                pitch: 64,
+            })
+            .unwrap();
+        }
+        sink.finish().unwrap();
+        let samples = out.get_ref()[44..]
+            .chunks(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect::<Vec<_>>();
+        assert_eq!(samples.len(), SAMPLE_RATE as usize);
+        let rising_edges = samples.windows(2).filter(|w| w[0] <= 0 && w[1] > 0).count();
+        assert!((499..=501).contains(&rising_edges), "{rising_edges}");
+    }
+}
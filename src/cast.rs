@@ -0,0 +1,97 @@
+use std::io::{self, Write};
+use std::time::Instant;
+
+/// Passes terminal output through to `inner` while recording it as an
+/// asciinema v2 cast. Everything written between two flushes becomes one
+/// output event, timestamped at the flush.
+#[derive(Debug)]
+pub struct CastWriter<W: Write, C: Write> {
+    inner: W,
+    cast: C,
+    start: Instant,
+    pending: Vec<u8>,
+}
+
+impl<W: Write, C: Write> CastWriter<W, C> {
+    pub fn new(inner: W, mut cast: C, width: u16, height: u16) -> io::Result<Self> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let term = std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string());
+        writeln!(
+            cast,
+            r#"{{"version": 2, "width": {width}, "height": {height}, "timestamp": {timestamp}, "env": {{"TERM": {}}}}}"#,
+            json_string(&term)
+        )?;
+        Ok(Self {
+            inner,
+            cast,
+            start: Instant::now(),
+            pending: Vec::new(),
+        })
+    }
+}
+
+impl<W: Write, C: Write> Write for CastWriter<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.pending.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        if !self.pending.is_empty() {
+            let elapsed = self.start.elapsed().as_secs_f64();
+            let data = String::from_utf8_lossy(&self.pending);
+            writeln!(self.cast, "[{elapsed:.6}, \"o\", {}]", json_string(&data))?;
+            self.pending.clear();
+        }
+        self.cast.flush()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7F}' => {
+                out.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cast_events() {
+        let mut screen = Vec::new();
+        let mut cast = Vec::new();
+        let mut writer = CastWriter::new(&mut screen, &mut cast, 80, 24).unwrap();
+        writer.write_all(b"\x1b[1;1H").unwrap();
+        writer.write_all("█\"".as_bytes()).unwrap();
+        writer.flush().unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        assert_eq!(screen, "\x1b[1;1H█\"".as_bytes());
+        let cast = String::from_utf8(cast).unwrap();
+        let lines = cast.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(r#"{"version": 2, "width": 80, "height": 24, "#));
+        assert!(lines[1].starts_with('['));
+        assert!(lines[1].ends_with(r#", "o", "\u001b[1;1H█\""]"#));
+    }
+}
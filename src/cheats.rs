@@ -0,0 +1,389 @@
+use std::fmt::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::{
+    chip8::Chip8,
+    condition::parse_number,
+    config::Config,
+    error::{Error, Result},
+    memory::Memory,
+    toml::{self, Value},
+};
+
+/// What a cheat holds steady.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Memory(u16),
+    Register(u8),
+}
+
+impl FromStr for Target {
+    type Err = Error;
+
+    /// `V3`, or a memory address in hex with or without `0x`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(x) = s.strip_prefix(['V', 'v'])
+            && x.len() == 1
+        {
+            return u8::from_str_radix(x, 16)
+                .map(Self::Register)
+                .map_err(|e| Error::Unknown(format!("bad register {s}: {e}")));
+        }
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        match u16::from_str_radix(hex, 16) {
+            Ok(addr) if (addr as usize) < Memory::MEMORY_SIZE => Ok(Self::Memory(addr)),
+            _ => Err(Error::Unknown(format!("bad address: {s}"))),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory(addr) => write!(f, "0x{addr:03X}"),
+            Self::Register(x) => write!(f, "V{x:X}"),
+        }
+    }
+}
+
+/// A byte of memory or a register frozen at `value`, such as `0x2F0=3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cheat {
+    pub target: Target,
+    pub value: u8,
+}
+
+impl FromStr for Cheat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (target, value) = s
+            .split_once('=')
+            .ok_or_else(|| Error::Unknown(format!("expected target=value: {s}")))?;
+        Ok(Self {
+            target: target.parse()?,
+            value: byte(value.trim())?,
+        })
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.target, self.value)
+    }
+}
+
+fn byte(value: &str) -> Result<u8> {
+    match parse_number(value)? {
+        value @ 0..=0xFF => Ok(value as u8),
+        _ => Err(Error::Unknown(format!("not a byte: {value}"))),
+    }
+}
+
+/// The frozen values for one ROM, rewritten at the start of every frame and
+/// saved under the ROM's SHA-1 in `cheats.toml` beside the config file.
+#[derive(Debug, Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+    /// The ROM's SHA-1, when the cheats are saved.
+    hash: Option<String>,
+}
+
+impl Cheats {
+    pub fn path() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join("cheats.toml"))
+    }
+
+    /// The cheats saved for the ROM with SHA-1 `hash`, which `save` writes
+    /// back to.
+    pub fn load(hash: &str) -> Result<Self> {
+        let text = match Self::path().filter(|path| path.exists()) {
+            Some(path) => std::fs::read_to_string(&path)
+                .map_err(|e| Error::Io(format!("failed to read {}: {e}", path.display())))?,
+            None => String::new(),
+        };
+        Ok(Self {
+            cheats: parse(&text, hash)?,
+            hash: Some(hash.to_string()),
+        })
+    }
+
+    /// Adds `cheats` on top, as given with `--cheat`.
+    pub fn with_overrides(mut self, cheats: &[Cheat]) -> Self {
+        for cheat in cheats {
+            self.set(*cheat);
+        }
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats.iter()
+    }
+
+    /// Adds `cheat`, replacing any other cheat on the same target.
+    pub fn set(&mut self, cheat: Cheat) {
+        match self.cheats.iter_mut().find(|c| c.target == cheat.target) {
+            Some(existing) => *existing = cheat,
+            None => self.cheats.push(cheat),
+        }
+    }
+
+    /// Removes the cheat on `target`, returning whether there was one.
+    pub fn remove(&mut self, target: Target) -> bool {
+        let len = self.cheats.len();
+        self.cheats.retain(|c| c.target != target);
+        self.cheats.len() != len
+    }
+
+    /// Writes the cheats back to the file they came from, keeping other
+    /// ROMs' sections. Does nothing for cheats that weren't loaded.
+    pub fn save(&self) -> Result<()> {
+        let (Some(hash), Some(path)) = (&self.hash, Self::path()) else {
+            return Ok(());
+        };
+        let text = match path.exists() {
+            true => std::fs::read_to_string(&path)?,
+            false => String::new(),
+        };
+        let text = merge(&text, hash, &self.cheats)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, text)
+            .map_err(|e| Error::Io(format!("failed to write {}: {e}", path.display())))
+    }
+
+    pub fn apply(&self, chip: &mut Chip8) {
+        for cheat in &self.cheats {
+            // Targets are checked when parsed
+            let _ = match cheat.target {
+                Target::Memory(addr) => chip.write_memory(addr, &[cheat.value]),
+                Target::Register(x) => chip.set_register(x, cheat.value),
+            };
+        }
+    }
+}
+
+/// The cheats in the `[rom."<hash>"]` section of a cheats file, written as
+/// `0x2F0 = 3` or `V3 = 9`.
+fn parse(text: &str, hash: &str) -> Result<Vec<Cheat>> {
+    let mut cheats = Vec::new();
+    for entry in toml::parse(text)? {
+        if !is_section(&entry.table, hash) {
+            continue;
+        }
+        let at_line = |e: Error| Error::Unknown(format!("line {}: {e}", entry.line));
+        let value = match entry.value {
+            Value::Integer(value @ 0..=0xFF) => value as u8,
+            value => return Err(at_line(Error::Unknown(format!("not a byte: {value}")))),
+        };
+        cheats.push(Cheat {
+            target: entry.key.parse().map_err(at_line)?,
+            value,
+        });
+    }
+    Ok(cheats)
+}
+
+fn is_section(table: &[String], hash: &str) -> bool {
+    matches!(table, [rom, h] if rom == "rom" && h.eq_ignore_ascii_case(hash))
+}
+
+/// `text` with the entries in the section for `hash` replaced by `cheats`,
+/// or a new section for them added at the end. Everything else, comments
+/// included, is left as it was.
+fn merge(text: &str, hash: &str, cheats: &[Cheat]) -> Result<String> {
+    // Don't rewrite a file that can't be read back
+    toml::parse(text)?;
+    let section = |out: &mut String| {
+        if !cheats.is_empty() {
+            let _ = writeln!(out, "[rom.\"{}\"]", hash.to_lowercase());
+        }
+        for cheat in cheats {
+            let _ = writeln!(out, "{} = {}", cheat.target, cheat.value);
+        }
+    };
+    let mut out = String::new();
+    let (mut ours, mut written) = (false, false);
+    for line in text.lines() {
+        if let Some(table) = toml::header(line) {
+            ours = is_section(&table, hash);
+            if ours {
+                if !written {
+                    section(&mut out);
+                    written = true;
+                }
+                continue;
+            }
+        }
+        // Only our entries go; comments and blank lines among them stay
+        let content = line.trim();
+        if !ours || content.is_empty() || content.starts_with('#') {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    if !written && !cheats.is_empty() {
+        if !out.is_empty() && !out.ends_with("\n\n") {
+            out.push('\n');
+        }
+        section(&mut out);
+    }
+    Ok(out)
+}
+
+/// A comparison for narrowing a RAM search, against the value at the last
+/// search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s {
+            "changed" => Ok(Self::Changed),
+            "unchanged" => Ok(Self::Unchanged),
+            "inc" | "increased" => Ok(Self::Increased),
+            "dec" | "decreased" => Ok(Self::Decreased),
+            _ => match s.strip_prefix('=') {
+                Some(value) => byte(value.trim()).map(Self::Equal),
+                None => Err(Error::Unknown(format!("unknown search: {s}"))),
+            },
+        }
+    }
+}
+
+/// Narrows down the addresses holding a value such as lives or score by
+/// comparing memory between searches.
+#[derive(Debug)]
+pub struct RamSearch {
+    previous: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    /// Starts with every address a candidate.
+    pub fn new(chip: &Chip8) -> Self {
+        Self {
+            previous: memory(chip),
+            candidates: (0..Memory::MEMORY_SIZE as u16).collect(),
+        }
+    }
+
+    /// Keeps the candidates that pass `filter` and remembers the current
+    /// values for the next search.
+    pub fn filter(&mut self, chip: &Chip8, filter: Filter) {
+        let current = memory(chip);
+        self.candidates.retain(|&addr| {
+            let (old, new) = (self.previous[addr as usize], current[addr as usize]);
+            match filter {
+                Filter::Equal(value) => new == value,
+                Filter::Changed => new != old,
+                Filter::Unchanged => new == old,
+                Filter::Increased => new > old,
+                Filter::Decreased => new < old,
+            }
+        });
+        self.previous = current;
+    }
+
+    /// The remaining addresses with their values at the last search.
+    pub fn candidates(&self) -> impl Iterator<Item = (u16, u8)> {
+        self.candidates
+            .iter()
+            .map(|&addr| (addr, self.previous[addr as usize]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+}
+
+fn memory(chip: &Chip8) -> Vec<u8> {
+    chip.read_memory(0, Memory::MEMORY_SIZE)
+        .map(<[u8]>::to_vec)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cheats() {
+        let cheat = "0x2F0=3".parse::<Cheat>().unwrap();
+        assert_eq!(cheat.target, Target::Memory(0x2F0));
+        assert_eq!("v3 = 0x10".parse::<Cheat>().unwrap().to_string(), "V3=16");
+        assert!("0x2F0=256".parse::<Cheat>().is_err());
+        assert!("0x1000=1".parse::<Cheat>().is_err());
+
+        let mut cheats = Cheats::default();
+        cheats.set(cheat);
+        cheats.set("V3=9".parse().unwrap());
+        cheats.set("2F0=5".parse().unwrap());
+        let mut chip = Chip8::default();
+        cheats.apply(&mut chip);
+        assert_eq!(chip.read_memory(0x2F0, 1).unwrap(), [5]);
+        assert_eq!(chip.registers()[3], 9);
+        assert!(cheats.remove(Target::Register(3)));
+        assert!(!cheats.remove(Target::Register(3)));
+    }
+
+    #[test]
+    fn test_cheat_file() {
+        let text = "# Pong\n[rom.\"abc\"]\n\"0x2F0\" = 3\n\n[rom.\"def\"]\nV3 = 9 # lives\n";
+        assert_eq!(
+            parse(text, "DEF").unwrap(),
+            [Cheat {
+                target: Target::Register(3),
+                value: 9
+            }]
+        );
+        let cheats = ["0x300=1".parse().unwrap(), "VA=2".parse().unwrap()];
+        let merged = merge(text, "def", &cheats).unwrap();
+        assert_eq!(
+            merged,
+            "# Pong\n[rom.\"abc\"]\n\"0x2F0\" = 3\n\n[rom.\"def\"]\n0x300 = 1\nVA = 2\n"
+        );
+        assert_eq!(parse(&merged, "def").unwrap(), cheats);
+        assert_eq!(
+            merge(&merged, "def", &[]).unwrap(),
+            "# Pong\n[rom.\"abc\"]\n\"0x2F0\" = 3\n\n"
+        );
+        assert_eq!(
+            merge(text, "123", &cheats[1..]).unwrap(),
+            format!("{text}\n[rom.\"123\"]\nVA = 2\n")
+        );
+        assert!(parse("[rom.\"abc\"]\nV3 = 300", "abc").is_err());
+    }
+
+    #[test]
+    fn test_ram_search() {
+        let mut chip = Chip8::default();
+        chip.write_memory(0x300, &[3, 3]).unwrap();
+        let mut search = RamSearch::new(&chip);
+        search.filter(&chip, "= 3".parse().unwrap());
+        assert_eq!(
+            search.candidates().collect::<Vec<_>>(),
+            [(0x300, 3), (0x301, 3)]
+        );
+        chip.write_memory(0x300, &[2]).unwrap();
+        search.filter(&chip, "dec".parse().unwrap());
+        assert_eq!(search.candidates().collect::<Vec<_>>(), [(0x300, 2)]);
+        search.filter(&chip, Filter::Unchanged);
+        assert_eq!(search.len(), 1);
+        search.filter(&chip, Filter::Changed);
+        assert_eq!(search.len(), 0);
+        assert!("bigger".parse::<Filter>().is_err());
+    }
+}
@@ -1,15 +1,26 @@
+use std::path::Path;
+
 use crate::{
+    audio::AudioFrame,
     display::DisplayBuffer,
-    error::Result,
+    error::{Error, Result},
+    image,
     input::Keypad,
-    memory::{Memory, OpCode},
+    memory::{Access, Memory, OpCode},
+    palette::Palette,
     program_counter::ProgramCounter,
-    register::{Register8BitArray, Register16Bit},
+    quirks::Quirks,
+    register::{Register8Bit, Register8BitArray, Register16Bit},
+    rng::Rng,
+    romdb,
     stack::Stack,
     timer::Timer,
 };
 
-#[derive(Debug)]
+/// Starts every save state, the last byte being the format version.
+const STATE_MAGIC: &[u8] = b"C8ST\x01";
+
+#[derive(Debug, Clone)]
 pub struct Chip8 {
     memory: Memory,
     stack: Stack,
@@ -19,6 +30,33 @@ pub struct Chip8 {
     pc: ProgramCounter,
     delay_timer: Timer,
     sound_timer: Timer,
+    rng: Rng,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: Register8Bit,
+    /// The bitplanes drawn to and cleared, one bit each, chosen by `FN01`.
+    planes: u8,
+    quirks: Quirks,
+    /// Set by `tick`, consumed by a `DXYN` under the display wait quirk.
+    vblank: bool,
+    waiting_for_vblank: bool,
+}
+
+/// The machine state outside memory and the display, small enough to save
+/// before every instruction for stepping back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuState {
+    stack: Stack,
+    index: Register16Bit,
+    registers: Register8BitArray,
+    pc: ProgramCounter,
+    delay_timer: Timer,
+    sound_timer: Timer,
+    rng: Rng,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: Register8Bit,
+    planes: u8,
+    vblank: bool,
+    waiting_for_vblank: bool,
 }
 
 impl Default for Chip8 {
@@ -32,12 +70,275 @@ impl Default for Chip8 {
             pc: ProgramCounter(Memory::PROGRAM_START),
             delay_timer: Timer::default(),
             sound_timer: Timer::default(),
+            rng: Rng::default(),
+            audio_pattern: None,
+            pitch: Self::DEFAULT_PITCH.into(),
+            planes: 1,
+            quirks: Quirks::default(),
+            vblank: false,
+            waiting_for_vblank: false,
         }
     }
 }
 
 impl Chip8 {
+    /// Pitch register value for 4000 Hz XO-CHIP pattern playback.
+    const DEFAULT_PITCH: u8 = 64;
+
+    pub fn with_rng(mut self, rng: Rng) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Advances the per-frame state; call at 60 Hz.
+    pub fn tick(&mut self) {
+        self.vblank = true;
+        self.waiting_for_vblank = false;
+        self.delay_timer.decrement();
+        self.sound_timer.decrement();
+        self.rng.tick();
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc.get()
+    }
+
+    pub fn index(&self) -> u16 {
+        self.index.get()
+    }
+
+    /// `V0` to `VF`.
+    pub fn registers(&self) -> [u8; 16] {
+        std::array::from_fn(|i| self.registers.get(i as u8).map_or(0, |r| r.get()))
+    }
+
+    /// Delay and sound timer values.
+    pub fn timers(&self) -> (u8, u8) {
+        (self.delay_timer.get(), self.sound_timer.get())
+    }
+
+    /// The instruction `cycle` will execute next.
+    pub fn next_opcode(&self) -> Result<OpCode> {
+        self.memory.read_opcode(self.pc.get())
+    }
+
+    /// Entry points of the subroutines being executed, outermost first, read
+    /// from the `CALL` before each return address on the stack.
+    pub fn call_stack(&self) -> Vec<u16> {
+        self.stack
+            .frames()
+            .map(|ret| {
+                self.memory
+                    .read_opcode(ret.wrapping_sub(2))
+                    .map_or(ret, |call| call.nnn())
+            })
+            .collect()
+    }
+
+    pub fn set_register(&mut self, x: u8, value: u8) -> Result<()> {
+        self.registers.get_mut(x)?.set(value);
+        Ok(())
+    }
+
+    pub fn set_index_register(&mut self, value: u16) {
+        self.index.set(value);
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc.set(value);
+    }
+
+    pub fn set_timers(&mut self, delay: u8, sound: u8) {
+        self.delay_timer.set(delay);
+        self.sound_timer.set(sound);
+    }
+
+    pub fn set_stack_depth(&mut self, depth: usize) {
+        self.stack.resize(depth);
+    }
+
+    /// Memory contents for debuggers, bypassing the access log.
+    pub fn read_memory(&self, addr: u16, len: usize) -> Result<&[u8]> {
+        self.memory.peek(addr, len)
+    }
+
+    pub fn write_memory(&mut self, addr: u16, data: &[u8]) -> Result<()> {
+        self.memory.write_slice(addr, data)
+    }
+
+    pub fn cpu_state(&self) -> CpuState {
+        CpuState {
+            stack: self.stack.clone(),
+            index: self.index,
+            registers: self.registers,
+            pc: self.pc,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            rng: self.rng.clone(),
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            planes: self.planes,
+            vblank: self.vblank,
+            waiting_for_vblank: self.waiting_for_vblank,
+        }
+    }
+
+    /// The CPU state for spotting a program stuck in a loop: the RNG's frame
+    /// counter changes every frame whatever the program does, so it's left
+    /// out.
+    pub fn loop_state(&self) -> CpuState {
+        CpuState {
+            rng: self.rng.without_frame_counter(),
+            ..self.cpu_state()
+        }
+    }
+
+    pub fn restore_cpu_state(&mut self, state: CpuState) {
+        self.stack = state.stack;
+        self.index = state.index;
+        self.registers = state.registers;
+        self.pc = state.pc;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.rng = state.rng;
+        self.audio_pattern = state.audio_pattern;
+        self.pitch = state.pitch;
+        self.planes = state.planes;
+        self.vblank = state.vblank;
+        self.waiting_for_vblank = state.waiting_for_vblank;
+    }
+
+    /// The whole machine as bytes: memory, registers, timers, stack, display
+    /// and the RNG. Quirks come from the command line, so they're left out.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = STATE_MAGIC.to_vec();
+        out.extend_from_slice(
+            self.memory
+                .peek(0usize, Memory::MEMORY_SIZE)
+                .expect("memory is MEMORY_SIZE bytes"),
+        );
+        out.extend(self.registers());
+        out.extend(self.index.get().to_be_bytes());
+        out.extend(self.pc.get().to_be_bytes());
+        out.extend([
+            self.delay_timer.get(),
+            self.sound_timer.get(),
+            self.pitch.get(),
+            self.planes,
+            self.vblank as u8,
+            self.waiting_for_vblank as u8,
+            self.audio_pattern.is_some() as u8,
+        ]);
+        out.extend(self.audio_pattern.unwrap_or_default());
+        for row in &self.display_buffer.pixels {
+            for plane in [1, 2] {
+                let bits = row
+                    .iter()
+                    .fold(0u64, |bits, index| bits << 1 | (index & plane != 0) as u64);
+                out.extend(bits.to_be_bytes());
+            }
+        }
+        let frames: Vec<u16> = self.stack.frames().collect();
+        out.push(frames.len() as u8);
+        out.extend(frames.iter().flat_map(|frame| frame.to_be_bytes()));
+        out.extend(self.rng.to_bytes());
+        out
+    }
+
+    /// Restores a machine saved by `save_state`, leaving it untouched if the
+    /// data doesn't parse.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<()> {
+        let mut data = data
+            .strip_prefix(STATE_MAGIC)
+            .ok_or_else(|| Error::Unknown("not a save state".to_string()))?;
+        let mut chip = self.clone();
+        chip.memory
+            .write_slice(0usize, &take::<{ Memory::MEMORY_SIZE }>(&mut data)?)?;
+        for (x, value) in take::<16>(&mut data)?.into_iter().enumerate() {
+            chip.registers.get_mut(x as u8)?.set(value);
+        }
+        chip.index = u16::from_be_bytes(take(&mut data)?).into();
+        chip.pc.set(u16::from_be_bytes(take(&mut data)?));
+        let [delay, sound, pitch, planes, vblank, waiting, has_pattern] = take(&mut data)?;
+        chip.delay_timer.set(delay);
+        chip.sound_timer.set(sound);
+        chip.pitch.set(pitch);
+        chip.planes = planes & 0x3;
+        chip.vblank = vblank != 0;
+        chip.waiting_for_vblank = waiting != 0;
+        let pattern = take(&mut data)?;
+        chip.audio_pattern = (has_pattern != 0).then_some(pattern);
+        for row in chip.display_buffer.pixels.iter_mut() {
+            row.fill(0);
+            for plane in [1, 2] {
+                let bits = u64::from_be_bytes(take(&mut data)?);
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    if bits >> (DisplayBuffer::WIDTH - 1 - x) & 1 != 0 {
+                        *pixel |= plane;
+                    }
+                }
+            }
+        }
+        chip.display_buffer.mark_dirty();
+        let [depth] = take(&mut data)?;
+        chip.stack = Stack::default();
+        for _ in 0..depth {
+            chip.stack.push(u16::from_be_bytes(take(&mut data)?));
+        }
+        chip.rng = Rng::from_bytes(data)?;
+        *self = chip;
+        Ok(())
+    }
+
+    /// Turns the memory access log on or off. It's off to begin with, as
+    /// nothing would drain it.
+    pub fn set_access_log(&mut self, on: bool) {
+        self.memory.set_logging(on);
+    }
+
+    /// The number of logged memory accesses, a mark for `accesses_since`.
+    pub fn access_count(&self) -> usize {
+        self.memory.access_count()
+    }
+
+    /// Memory accesses logged after `access_count` returned `mark`, without
+    /// draining them.
+    pub fn accesses_since(&self, mark: usize) -> Vec<Access> {
+        self.memory.accesses_since(mark)
+    }
+
+    /// Memory reads and writes made by instructions since the last call.
+    pub fn take_accesses(&self) -> Vec<Access> {
+        self.memory.take_accesses()
+    }
+
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer.get() > 0
+    }
+
+    /// Saves the current frame as an image, format chosen by the extension.
+    pub fn screenshot(&self, path: &Path, scale: usize, palette: &Palette) -> Result<()> {
+        image::save_image(path, &self.display_buffer, scale, palette)
+    }
+
+    pub fn audio_frame(&self) -> AudioFrame {
+        AudioFrame {
+            playing: self.sound_active(),
+            pattern: self.audio_pattern,
+            pitch: self.pitch.get(),
+        }
+    }
+
     pub fn cycle(&mut self, keypad: &Keypad) -> Result<()> {
+        if self.waiting_for_vblank {
+            return Ok(());
+        }
+
         // FETCH
         let opcode = self.memory.read_opcode(self.pc.get())?;
         self.pc.increment();
@@ -84,11 +385,14 @@ impl Chip8 {
                 _ => self.unknown_opcode(opcode)?,
             },
             0xF => match opcode.nn() {
+                0x02 if opcode.x() == 0 => self.load_audio_pattern(opcode)?,
                 0x07 => self.set_x_to_delay(opcode)?,
                 0x15 => self.set_delay(opcode)?,
                 0x18 => self.set_sound(opcode)?,
                 0x1E => self.add_x_to_index(opcode)?,
                 0x29 => self.set_index_to_font(opcode)?,
+                0x01 => self.select_planes(opcode)?,
+                0x3A => self.set_pitch(opcode)?,
                 0x33 => self.bcd_x_in_index(opcode)?,
                 0x55 => self.set_x_in_index_spread(opcode)?,
                 0x65 => self.read_x_from_index_spread(opcode)?,
@@ -126,7 +430,8 @@ impl Chip8 {
     }
 
     pub fn load_rom(&mut self, rom: &[u8]) -> Result<()> {
-        self.memory.write_slice(Memory::PROGRAM_START, rom)
+        self.memory
+            .write_slice(Memory::PROGRAM_START, romdb::program(rom))
     }
 
     fn subtract(&mut self, left: u8, right: u8) -> Result<(u8, bool)> {
@@ -158,40 +463,64 @@ impl Chip8 {
     }
 
     fn clear_display_buffer(&mut self, _opcode: OpCode) -> Result<()> {
-        self.display_buffer.clear()?;
+        self.display_buffer.clear(self.planes)?;
         Ok(())
     }
 
     fn update_display_buffer(&mut self, opcode: OpCode) -> Result<()> {
+        if self.quirks.display_wait && !self.vblank {
+            // Stall on this instruction until the next frame boundary
+            self.waiting_for_vblank = true;
+            self.pc.decrement();
+            return Ok(());
+        }
+        self.vblank = false;
         let start_x = self.registers.get(opcode.x())?.get() % DisplayBuffer::WIDTH as u8;
-        let mut y = self.registers.get(opcode.y())?.get() % DisplayBuffer::HEIGHT as u8;
+        let start_y = self.registers.get(opcode.y())?.get() % DisplayBuffer::HEIGHT as u8;
         let n = opcode.n();
         self.registers.get_mut(0xF)?.set(0); // Reset collision flag
-        for i in 0..n {
-            let sprite = self.memory.read(self.index.get() + i as u16)?;
-            let mut x = start_x; // Reset x for each row
-            for bit_mask in [128, 64, 32, 16, 8, 4, 2, 1] {
-                if x as usize >= DisplayBuffer::WIDTH {
-                    break; // Clip at screen edge
+        // With both planes selected the sprite holds N rows for each in turn
+        let selected = [1, 2].into_iter().filter(|plane| self.planes & plane != 0);
+        for (sprite_start, plane) in (0..).step_by(n as usize).zip(selected) {
+            for i in 0..n {
+                let sprite = self
+                    .memory
+                    .read(self.index.get() + sprite_start + i as u16)?;
+                let mut y = (start_y + i) as usize;
+                if y >= DisplayBuffer::HEIGHT {
+                    if !self.quirks.wrap_sprites {
+                        break; // Clip at screen edge
+                    }
+                    y %= DisplayBuffer::HEIGHT;
                 }
-                if sprite & bit_mask != 0 {
-                    if self.display_buffer.is_on(x, y)? {
-                        self.display_buffer.set(x, y, false)?;
-                        self.registers.get_mut(0xF)?.set(1); // Collision detected
-                    } else {
-                        self.display_buffer.set(x, y, true)?;
+                for (x, bit_mask) in (start_x as usize..).zip([128, 64, 32, 16, 8, 4, 2, 1]) {
+                    let mut x = x;
+                    if x >= DisplayBuffer::WIDTH {
+                        if !self.quirks.wrap_sprites {
+                            break; // Clip at screen edge
+                        }
+                        x %= DisplayBuffer::WIDTH;
+                    }
+                    if sprite & bit_mask != 0 {
+                        let index = self.display_buffer.get(x, y)?;
+                        if index & plane != 0 {
+                            self.registers.get_mut(0xF)?.set(1); // Collision detected
+                        }
+                        self.display_buffer.set(x, y, index ^ plane)?;
                     }
                 }
-                x += 1;
-            }
-            y += 1;
-            if y as usize >= DisplayBuffer::HEIGHT {
-                break;
             }
         }
         Ok(())
     }
 
+    /// `FN01`: XO-CHIP's plane selection, bit 0 for the first plane and bit 1
+    /// for the second.
+    fn select_planes(&mut self, opcode: OpCode) -> Result<()> {
+        self.planes = opcode.x() & 0x3;
+        Ok(())
+    }
+
     fn shift(&mut self, opcode: OpCode, dir: Dir) -> Result<()> {
         let vx = self.registers.get(opcode.x())?.get();
         self.registers.get_mut(opcode.x())?.set(match dir {
@@ -295,7 +624,7 @@ impl Chip8 {
     fn random_and(&mut self, opcode: OpCode) -> Result<()> {
         self.registers
             .get_mut(opcode.x())?
-            .set(rand::random::<u8>() & opcode.nn());
+            .set(self.rng.next_byte() & opcode.nn());
         Ok(())
     }
 
@@ -344,6 +673,20 @@ impl Chip8 {
         Ok(())
     }
 
+    fn load_audio_pattern(&mut self, _opcode: OpCode) -> Result<()> {
+        let mut pattern = [0; 16];
+        for (j, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.memory.read(self.index.get() + j as u16)?;
+        }
+        self.audio_pattern = Some(pattern);
+        Ok(())
+    }
+
+    fn set_pitch(&mut self, opcode: OpCode) -> Result<()> {
+        self.pitch.set(self.registers.get(opcode.x())?.get());
+        Ok(())
+    }
+
     fn add_x_to_index(&mut self, opcode: OpCode) -> Result<()> {
         self.index
             .set(self.index.get() + self.registers.get(opcode.x())?.get() as u16);
@@ -402,9 +745,19 @@ pub enum Dir {
     Right,
 }
 
+/// Splits the next `N` bytes off a save state.
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N]> {
+    let (head, tail) = data
+        .split_first_chunk()
+        .ok_or_else(|| Error::Unknown("save state is truncated".to_string()))?;
+    *data = tail;
+    Ok(*head)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rng::RngKind;
 
     #[test]
     fn test_chip8() {
@@ -412,6 +765,108 @@ mod tests {
         assert_eq!(chip8.pc.get(), 0x200);
     }
 
+    /// The values of `V0 := random 0xFF` on a loop, ticking every few.
+    fn random_bytes(chip8: &mut Chip8, count: usize) -> Vec<u8> {
+        (0..count)
+            .map(|i| {
+                chip8.cycle(&Keypad::default()).unwrap();
+                chip8.cycle(&Keypad::default()).unwrap();
+                if i % 3 == 0 {
+                    chip8.tick();
+                }
+                chip8.registers()[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_random_and_is_seeded() {
+        // V0 := random 0xFF; V1 := random 0x0F; jump 0x200
+        let rom = [0xC0, 0xFF, 0xC1, 0x0F, 0x12, 0x00];
+        for kind in [RngKind::Xorshift, RngKind::Vip] {
+            let run = |seed| {
+                let mut chip8 = Chip8::default().with_rng(Rng::new(kind, seed));
+                chip8.load_rom(&rom).unwrap();
+                (0..32)
+                    .map(|_| {
+                        for _ in 0..3 {
+                            chip8.cycle(&Keypad::default()).unwrap();
+                        }
+                        chip8.tick();
+                        (chip8.registers()[0], chip8.registers()[1])
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let sequence = run(1234);
+            assert_eq!(sequence, run(1234), "{kind:?}");
+            assert_ne!(sequence, run(5678), "{kind:?}");
+            assert!(
+                sequence.iter().all(|&(_, masked)| masked <= 0x0F),
+                "{kind:?}"
+            );
+            assert!(
+                sequence.iter().any(|&(value, _)| value != sequence[0].0),
+                "{kind:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_state() {
+        // V0 := random 0xFF; jump 0x200
+        let rom = [0xC0, 0xFF, 0x12, 0x00];
+        for kind in [RngKind::Xorshift, RngKind::Vip] {
+            let mut chip8 = Chip8::default().with_rng(Rng::new(kind, 42));
+            chip8.load_rom(&rom).unwrap();
+            random_bytes(&mut chip8, 5);
+            chip8.display_buffer.set(3usize, 7, 2).unwrap();
+            chip8.stack.push(0x234);
+            let state = chip8.save_state();
+            let ahead = random_bytes(&mut chip8, 20);
+
+            let mut restored = Chip8::default();
+            restored.load_state(&state).unwrap();
+            assert_eq!(restored.save_state(), state);
+            assert_eq!(restored.display_buffer.pixels[7][3], 2);
+            assert_eq!(random_bytes(&mut restored, 20), ahead, "{kind:?}");
+        }
+        let mut chip8 = Chip8::default();
+        let state = chip8.save_state();
+        assert!(chip8.load_state(&state[..state.len() - 1]).is_err());
+        assert!(chip8.load_state(b"not a state").is_err());
+    }
+
+    #[test]
+    fn test_tick_counts_down_timers() {
+        let mut chip8 = Chip8::default();
+        chip8.load_rom(&[0x60, 0x02, 0xF0, 0x18]).unwrap();
+        chip8.cycle(&Keypad::default()).unwrap();
+        chip8.cycle(&Keypad::default()).unwrap();
+        assert!(chip8.sound_active());
+        chip8.tick();
+        assert!(chip8.sound_active());
+        chip8.tick();
+        assert!(!chip8.sound_active());
+        chip8.tick();
+        assert_eq!(chip8.sound_timer.get(), 0);
+    }
+
+    #[test]
+    fn test_xo_chip_audio_registers() {
+        let mut chip8 = Chip8::default();
+        // I := 0x20A; F002; V0 := 0x70; F03A; data
+        let mut rom = vec![0xA2, 0x0A, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A, 0x00, 0x00];
+        rom.extend(0u8..16);
+        chip8.load_rom(&rom).unwrap();
+        assert_eq!(chip8.audio_frame().pattern, None);
+        for _ in 0..4 {
+            chip8.cycle(&Keypad::default()).unwrap();
+        }
+        let frame = chip8.audio_frame();
+        assert_eq!(frame.pattern, Some(core::array::from_fn(|i| i as u8)));
+        assert_eq!(frame.pitch, 0x70);
+    }
+
     #[test]
     fn test_subtract_x_y() {}
 
@@ -421,6 +876,105 @@ mod tests {
     #[test]
     fn test_update_display_buffer() {}
 
+    #[test]
+    fn test_bitplanes() {
+        let rom = [
+            0xF3, 0x01, // planes 3
+            0xA2, 0x0E, // I := 0x20E
+            0xD0, 0x01, // one row on each plane
+            0xD0, 0x01, // again, both planes collide
+            0xF2, 0x01, // plane 2
+            0x00, 0xE0, // clear just plane 2
+            0x12, 0x0C, // loop
+            0xC0, 0xA0, // plane 1 row, plane 2 row
+        ];
+        let mut chip8 = Chip8::default();
+        chip8.load_rom(&rom).unwrap();
+        let row = |chip8: &Chip8| chip8.display_buffer.pixels[0][..3].to_vec();
+        for _ in 0..3 {
+            chip8.cycle(&Keypad::default()).unwrap();
+        }
+        assert_eq!(row(&chip8), [3, 1, 2]);
+        assert_eq!(chip8.registers()[0xF], 0);
+        chip8.cycle(&Keypad::default()).unwrap();
+        assert_eq!(row(&chip8), [0, 0, 0]);
+        assert_eq!(chip8.registers()[0xF], 1);
+        chip8.display_buffer.pixels[0][..3].copy_from_slice(&[3, 1, 2]);
+        for _ in 0..2 {
+            chip8.cycle(&Keypad::default()).unwrap();
+        }
+        assert_eq!(row(&chip8), [1, 1, 0]);
+    }
+
+    /// Draws the font sprite for `0` (a 4x5 box) at `(x, y)`.
+    fn draw_box_at(quirks: Quirks, x: u8, y: u8) -> Chip8 {
+        let mut chip8 = Chip8::default().with_quirks(quirks);
+        chip8
+            .load_rom(&[0x60, x, 0x61, y, 0xA0, 0x50, 0xD0, 0x15])
+            .unwrap();
+        for _ in 0..4 {
+            chip8.cycle(&Keypad::default()).unwrap();
+        }
+        chip8
+    }
+
+    #[test]
+    fn test_sprite_clipping() {
+        let chip8 = draw_box_at(Quirks::default(), 62, 29);
+        let lit = |x: usize, y: usize| chip8.display_buffer.get(x, y).unwrap() != 0;
+        assert!(lit(62, 29) && lit(63, 29) && lit(62, 31));
+        assert!(!lit(0, 29) && !lit(1, 29));
+        assert!(!lit(62, 0) && !lit(63, 1));
+    }
+
+    #[test]
+    fn test_sprite_wrapping() {
+        let quirks = Quirks {
+            wrap_sprites: true,
+            ..Default::default()
+        };
+        let chip8 = draw_box_at(quirks, 62, 29);
+        let lit = |x: usize, y: usize| chip8.display_buffer.get(x, y).unwrap() != 0;
+        assert!(lit(62, 29) && lit(63, 29) && lit(0, 29) && lit(1, 29));
+        assert!(lit(62, 30) && !lit(63, 30) && !lit(0, 30) && lit(1, 30));
+        assert!(lit(62, 1) && lit(63, 1) && lit(0, 1) && lit(1, 1));
+        assert!(!lit(2, 29) && !lit(62, 2));
+        // Starting coordinates still wrap regardless of the quirk
+        let chip8 = draw_box_at(Quirks::default(), 64 + 3, 32 + 4);
+        assert_eq!(chip8.display_buffer.get(3usize, 4).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_display_wait() {
+        let quirks = Quirks {
+            display_wait: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::default().with_quirks(quirks);
+        // I := font 0; draw twice
+        chip8
+            .load_rom(&[0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05])
+            .unwrap();
+        let keypad = Keypad::default();
+        for _ in 0..3 {
+            chip8.cycle(&keypad).unwrap();
+        }
+        assert_eq!(chip8.pc.get(), 0x202);
+        assert_eq!(chip8.display_buffer.get(0usize, 0).unwrap(), 0);
+
+        chip8.tick();
+        chip8.cycle(&keypad).unwrap();
+        assert_eq!(chip8.display_buffer.get(0usize, 0).unwrap(), 1);
+        chip8.cycle(&keypad).unwrap();
+        chip8.cycle(&keypad).unwrap();
+        assert_eq!(chip8.pc.get(), 0x204);
+
+        chip8.tick();
+        chip8.cycle(&keypad).unwrap();
+        assert_eq!(chip8.display_buffer.get(0usize, 0).unwrap(), 0);
+        assert_eq!(chip8.pc.get(), 0x206);
+    }
+
     #[test]
     fn test_shift() {}
 }
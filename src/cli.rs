@@ -0,0 +1,605 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::{
+    audio::ToneConfig,
+    cheats::Cheat,
+    config::Config,
+    error::{Error, Result},
+    flicker::FlickerMode,
+    graphics::GraphicsProtocol,
+    input::Keymap,
+    palette::{ColorMode, Palette, Rgb},
+    quirks::{Platform, Quirks},
+    renderer::Renderer,
+    rng::RngKind,
+    romdb,
+    runner::Runner,
+    sha1::sha1_hex,
+};
+
+/// Options of the `run` and `test` subcommands.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    pub rom: PathBuf,
+    pub platform: Option<Platform>,
+    /// Individual quirks switched on or off on top of the platform profile.
+    pub quirks: Vec<(String, bool)>,
+    pub ipf: usize,
+    pub seed: Option<u64>,
+    pub rng: RngKind,
+    pub keymap: Keymap,
+    pub audio: String,
+    pub tone: ToneConfig,
+    pub renderer: Option<Renderer>,
+    pub graphics: Option<GraphicsProtocol>,
+    pub palette: Option<Palette>,
+    /// `--bg`/`--fg` overrides of palette entries 0 and 1.
+    pub colors: Vec<(usize, Rgb)>,
+    pub color_mode: ColorMode,
+    pub flicker: Option<FlickerMode>,
+    pub scale: usize,
+    pub screenshot_format: String,
+    pub record: Option<PathBuf>,
+    pub cast: Option<PathBuf>,
+    pub headless: bool,
+    pub frames: Option<u64>,
+    pub trace: Option<PathBuf>,
+    pub profile: Option<PathBuf>,
+    pub paused: bool,
+    pub gdb: Option<u16>,
+    pub cheats: Vec<Cheat>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            rom: PathBuf::new(),
+            platform: None,
+            quirks: Vec::new(),
+            ipf: Runner::DEFAULT_IPF,
+            seed: None,
+            rng: RngKind::default(),
+            keymap: Keymap::default(),
+            audio: "bell".to_string(),
+            tone: ToneConfig::default(),
+            renderer: None,
+            graphics: None,
+            palette: None,
+            colors: Vec::new(),
+            color_mode: ColorMode::detect(),
+            flicker: None,
+            scale: 8,
+            screenshot_format: "png".to_string(),
+            record: None,
+            cast: None,
+            headless: false,
+            frames: None,
+            trace: None,
+            profile: None,
+            paused: false,
+            gdb: None,
+            cheats: Vec::new(),
+        }
+    }
+}
+
+/// Flags of `run` and `test`: name, value placeholder (empty for switches)
+/// and help text.
+const RUN_FLAGS: &[(&str, &str, &str)] = &[
+    (
+        "--platform",
+        "chip8|schip|xochip",
+        "quirk profile to emulate",
+    ),
+    (
+        "--quirk",
+        "name=on|off",
+        "override one quirk: display_wait, wrap_sprites",
+    ),
+    ("--ipf", "n", "instructions per 60 Hz frame (default 15)"),
+    ("--seed", "n", "seed the random number generator"),
+    ("--rng", "xorshift|vip", "random number generator"),
+    (
+        "--keymap",
+        "keys",
+        "16 keyboard keys for keys 0-F (default x123qweasdzc4rfv)",
+    ),
+    (
+        "--renderer",
+        "auto|block|double|half|braille|sixel|kitty",
+        "how to draw the display",
+    ),
+    (
+        "--scale",
+        "n",
+        "pixel size for images, recordings and bitmap renderers (default 8)",
+    ),
+    (
+        "--palette",
+        "classic|green|amber|octo|lcd",
+        "colour palette",
+    ),
+    ("--fg", "#rrggbb", "foreground colour"),
+    ("--bg", "#rrggbb", "background colour"),
+    (
+        "--256",
+        "",
+        "use the 256 colour palette instead of true colour",
+    ),
+    (
+        "--flicker",
+        "off|blend[:n]|phosphor[:decay]",
+        "sprite flicker reduction",
+    ),
+    ("--audio", "bell|none|wav:path", "sound output"),
+    ("--tone", "hz", "buzzer frequency"),
+    ("--volume", "0-1", "buzzer volume"),
+    (
+        "--waveform",
+        "square|sine|triangle|sawtooth",
+        "buzzer waveform",
+    ),
+    (
+        "--screenshot-format",
+        "png|ppm|pbm",
+        "format of F12 screenshots",
+    ),
+    (
+        "--record",
+        "out.gif",
+        "record the display as an animated GIF",
+    ),
+    (
+        "--cast",
+        "out.cast",
+        "record the terminal as an asciinema cast",
+    ),
+    (
+        "--headless",
+        "",
+        "run without a terminal and print the final frame",
+    ),
+    (
+        "--frames",
+        "n",
+        "stop after n frames, or sooner if the program halts",
+    ),
+    ("--trace", "file", "log every executed instruction"),
+    (
+        "--profile",
+        "file",
+        "write a hot-spot and coverage report, plus a .folded flamegraph file",
+    ),
+    (
+        "--paused",
+        "",
+        "start paused in the debugger (F5 pauses while running)",
+    ),
+    (
+        "--gdb",
+        "port",
+        "run headless under a GDB remote debugger on localhost:port",
+    ),
+    (
+        "--cheat",
+        "addr=value",
+        "freeze a memory byte, or a register as Vx=value, every frame",
+    ),
+    (
+        "--config",
+        "file",
+        "config file (default $XDG_CONFIG_HOME/chip8/config.toml)",
+    ),
+];
+
+/// Extra flags of `test`.
+const TEST_FLAGS: &[(&str, &str, &str)] = &[
+    (
+        "--expect",
+        "golden.pbm",
+        "compare the final frame against a bitmap",
+    ),
+    (
+        "--update",
+        "",
+        "write the final frame to the --expect file instead",
+    ),
+];
+
+#[derive(Debug)]
+pub enum Command {
+    Run(RunArgs),
+    /// Runs headless and checks the final frame against a golden bitmap.
+    Test {
+        args: RunArgs,
+        expect: Option<PathBuf>,
+        update: bool,
+    },
+    Disasm {
+        rom: PathBuf,
+        /// A `--trace` log whose addresses resolve `BNNN` jump targets.
+        coverage: Option<PathBuf>,
+        cfg: Option<PathBuf>,
+        calls: Option<PathBuf>,
+    },
+    Asm {
+        source: PathBuf,
+        output: PathBuf,
+    },
+    Info(PathBuf),
+    Help(String),
+}
+
+/// A ROM and the flags given for it on the command line, resolved into
+/// `RunOptions` once the config file and ROM are loaded.
+#[derive(Debug)]
+pub struct RunArgs {
+    pub rom: PathBuf,
+    pub config: Option<PathBuf>,
+    flags: Vec<(String, String)>,
+}
+
+impl RunArgs {
+    /// Layers the ROM database's recommendations, the config defaults, the
+    /// ROM's section of the config and finally the command line flags.
+    pub fn options(&self, config: &Config, rom: &[u8]) -> Result<RunOptions> {
+        let mut options = RunOptions::default();
+        let recommended = romdb::lookup(rom)
+            .map(|info| info.flags)
+            .unwrap_or_default();
+        for (flag, value) in recommended
+            .iter()
+            .chain(config.flags_for(&sha1_hex(rom)))
+            .chain(&self.flags)
+        {
+            options.apply(flag, value)?;
+        }
+        options.rom = self.rom.clone();
+        Ok(options)
+    }
+}
+
+impl RunOptions {
+    /// The quirks to emulate: the platform profile with any overrides.
+    pub fn quirks(&self) -> Quirks {
+        let mut quirks = self.platform.map(|p| p.quirks()).unwrap_or_default();
+        for (name, on) in &self.quirks {
+            // Names were checked when the override was added
+            let _ = quirks.set(name, *on);
+        }
+        quirks
+    }
+
+    /// Applies one `--flag value` pair; switches get an empty value.
+    pub fn apply(&mut self, flag: &str, value: &str) -> Result<()> {
+        match flag {
+            "--platform" => self.platform = Some(value.parse()?),
+            "--quirk" => {
+                let (name, on) = match value.split_once('=') {
+                    Some((name, "on" | "true")) => (name, true),
+                    Some((name, "off" | "false")) => (name, false),
+                    _ => return Err(Error::Unknown(format!("bad value for {flag}: {value}"))),
+                };
+                Quirks::default().set(name, on)?;
+                self.quirks.push((name.to_string(), on));
+            }
+            "--ipf" => self.ipf = number(flag, value)?,
+            "--seed" => self.seed = Some(number(flag, value)?),
+            "--rng" => self.rng = value.parse()?,
+            "--keymap" => self.keymap = value.parse()?,
+            "--renderer" => {
+                (self.renderer, self.graphics) = match value {
+                    "auto" => (None, None),
+                    "sixel" | "kitty" => (None, Some(value.parse()?)),
+                    name => (Some(name.parse()?), None),
+                }
+            }
+            "--scale" => self.scale = number(flag, value)?,
+            "--palette" => self.palette = Some(Palette::named(value)?),
+            "--fg" => self.colors.push((1, value.parse()?)),
+            "--bg" => self.colors.push((0, value.parse()?)),
+            "--256" => self.color_mode = ColorMode::Ansi256,
+            "--flicker" => {
+                self.flicker = match value {
+                    "off" => None,
+                    mode => Some(mode.parse()?),
+                }
+            }
+            "--audio" => self.audio = value.to_string(),
+            "--tone" => self.tone.frequency = number(flag, value)?,
+            "--volume" => self.tone.volume = number(flag, value)?,
+            "--waveform" => self.tone.waveform = value.parse()?,
+            "--screenshot-format" => self.screenshot_format = value.to_string(),
+            "--record" => self.record = Some(value.into()),
+            "--cast" => self.cast = Some(value.into()),
+            "--headless" => self.headless = true,
+            "--frames" => self.frames = Some(number(flag, value)?),
+            "--trace" => self.trace = Some(value.into()),
+            "--profile" => self.profile = Some(value.into()),
+            "--paused" => self.paused = true,
+            "--gdb" => self.gdb = Some(number(flag, value)?),
+            "--cheat" => self.cheats.push(value.parse()?),
+            _ => return Err(Error::Unknown(format!("unknown option: {flag}"))),
+        }
+        Ok(())
+    }
+}
+
+fn number<T: FromStr>(flag: &str, value: &str) -> Result<T>
+where
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|e| Error::Unknown(format!("bad value for {flag}: {value}: {e}")))
+}
+
+/// Parses the arguments after the program name. A bare ROM path is
+/// shorthand for `run`.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command> {
+    let mut args = args.into_iter().peekable();
+    let subcommand = match args.peek().map(String::as_str) {
+        None | Some("-h" | "--help" | "help") => return Ok(Command::Help(usage())),
+        Some(name @ ("run" | "test" | "disasm" | "asm" | "info")) => {
+            let name = name.to_string();
+            args.next();
+            name
+        }
+        Some(_) => "run".to_string(),
+    };
+    let args = args.collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        return Ok(Command::Help(subcommand_help(&subcommand)));
+    }
+    match subcommand.as_str() {
+        "run" => parse_run(&args, &[]).map(|(args, _)| Command::Run(args)),
+        "test" => {
+            let (args, extra) = parse_run(&args, TEST_FLAGS)?;
+            let mut expect = None;
+            let mut update = false;
+            for (flag, value) in extra {
+                match flag.as_str() {
+                    "--expect" => expect = Some(PathBuf::from(value)),
+                    "--update" => update = true,
+                    _ => return Err(Error::Unknown(format!("unknown option: {flag}"))),
+                }
+            }
+            // Updates are saved in the format the extension names, and golden
+            // frames are read back as PBM
+            if let Some(path) = &expect
+                && path.extension().is_none_or(|extension| extension != "pbm")
+            {
+                return Err(Error::Unknown(format!(
+                    "--expect needs a .pbm file: {}",
+                    path.display()
+                )));
+            }
+            if update && expect.is_none() {
+                return Err(Error::Unknown("--update needs --expect".to_string()));
+            }
+            Ok(Command::Test {
+                args,
+                expect,
+                update,
+            })
+        }
+        "disasm" => parse_disasm(&args),
+        "info" => Ok(Command::Info(single_path(&subcommand, &args)?)),
+        _ => {
+            let (source, output) = match args.as_slice() {
+                [source] => (
+                    PathBuf::from(source),
+                    PathBuf::from(source).with_extension("ch8"),
+                ),
+                [source, flag, output] if flag == "-o" => (source.into(), output.into()),
+                _ => return Err(Error::Unknown(subcommand_help("asm"))),
+            };
+            Ok(Command::Asm { source, output })
+        }
+    }
+}
+
+fn parse_disasm(args: &[String]) -> Result<Command> {
+    let (mut rom, mut coverage, mut cfg, mut calls) = (None, None, None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let slot = match arg.as_str() {
+            "--coverage" => &mut coverage,
+            "--cfg" => &mut cfg,
+            "--calls" => &mut calls,
+            _ if !arg.starts_with('-') && rom.is_none() => {
+                rom = Some(PathBuf::from(arg));
+                continue;
+            }
+            _ => return Err(Error::Unknown(subcommand_help("disasm"))),
+        };
+        let value = args
+            .next()
+            .ok_or_else(|| Error::Unknown(format!("{arg} expects a file")))?;
+        *slot = Some(PathBuf::from(value));
+    }
+    let rom = rom.ok_or_else(|| Error::Unknown(subcommand_help("disasm")))?;
+    Ok(Command::Disasm {
+        rom,
+        coverage,
+        cfg,
+        calls,
+    })
+}
+
+/// Parses `run` style arguments, returning the flags from `extra` separately.
+/// Flags are checked here but only applied once the config is loaded.
+fn parse_run(
+    args: &[String],
+    extra: &[(&str, &str, &str)],
+) -> Result<(RunArgs, Vec<(String, String)>)> {
+    let mut scratch = RunOptions::default();
+    let mut flags = Vec::new();
+    let mut config = None;
+    let mut extra_values = Vec::new();
+    let mut rom = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if rom.replace(PathBuf::from(arg)).is_some() {
+                return Err(Error::Unknown(format!("unexpected argument: {arg}")));
+            }
+            continue;
+        }
+        let Some((flag, placeholder, _)) =
+            RUN_FLAGS.iter().chain(extra).find(|(flag, ..)| flag == arg)
+        else {
+            return Err(Error::Unknown(format!("unknown option: {arg}")));
+        };
+        let value = match placeholder.is_empty() {
+            true => "",
+            false => args
+                .next()
+                .ok_or_else(|| Error::Unknown(format!("{flag} needs a value")))?,
+        };
+        if extra.iter().any(|(f, ..)| f == flag) {
+            extra_values.push((flag.to_string(), value.to_string()));
+        } else if *flag == "--config" {
+            config = Some(PathBuf::from(value));
+        } else {
+            scratch.apply(flag, value)?;
+            flags.push((flag.to_string(), value.to_string()));
+        }
+    }
+    let rom = rom.ok_or_else(|| Error::Unknown("missing ROM path, see --help".to_string()))?;
+    let args = RunArgs { rom, config, flags };
+    Ok((args, extra_values))
+}
+
+fn single_path(subcommand: &str, args: &[String]) -> Result<PathBuf> {
+    match args {
+        [path] => Ok(path.into()),
+        _ => Err(Error::Unknown(subcommand_help(subcommand))),
+    }
+}
+
+fn usage() -> String {
+    "usage: chip8 <command> [options]\n\
+     \n\
+     commands:\n\
+     \x20 run <rom>          run a ROM in the terminal (the default: chip8 <rom>)\n\
+     \x20 test <rom>         run headless and compare the final frame\n\
+     \x20 disasm <rom>       print a disassembly listing\n\
+     \x20 asm <source>       assemble a listing back into a ROM\n\
+     \x20 info <rom>         show details about a ROM\n\
+     \n\
+     run `chip8 <command> --help` for the options of a command"
+        .to_string()
+}
+
+fn subcommand_help(subcommand: &str) -> String {
+    let flags = |flags: &[(&str, &str, &str)]| {
+        flags
+            .iter()
+            .map(|(flag, placeholder, help)| {
+                let name = match placeholder.is_empty() {
+                    true => flag.to_string(),
+                    false => format!("{flag} <{placeholder}>"),
+                };
+                format!("  {name:<32} {help}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    match subcommand {
+        "run" => format!(
+            "usage: chip8 run [options] <rom>\n\noptions:\n{}",
+            flags(RUN_FLAGS)
+        ),
+        "test" => format!(
+            "usage: chip8 test [options] <rom>\n\noptions:\n{}\n{}",
+            flags(TEST_FLAGS),
+            flags(RUN_FLAGS)
+        ),
+        "disasm" => "usage: chip8 disasm [options] <rom>\n\n\
+             options:\n\
+             \x20 --cfg <out.dot>                  write the control-flow graph as Graphviz DOT\n\
+             \x20 --calls <out.dot>                write the call graph as Graphviz DOT\n\
+             \x20 --coverage <trace>               resolve indirect jumps from a --trace log"
+            .to_string(),
+        "asm" => "usage: chip8 asm <source> [-o <out.ch8>]".to_string(),
+        "info" => "usage: chip8 info <rom>".to_string(),
+        _ => usage(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_run() {
+        let Command::Run(run) = parse(args(
+            "--ipf 30 --paused game.ch8 --platform xochip --cheat V3=9",
+        ))
+        .unwrap() else {
+            panic!("expected run");
+        };
+        let options = run.options(&Config::default(), &[]).unwrap();
+        assert_eq!(options.rom, PathBuf::from("game.ch8"));
+        assert_eq!(options.ipf, 30);
+        assert!(options.paused);
+        assert_eq!(options.platform, Some(Platform::XoChip));
+        assert_eq!(options.cheats, ["V3=9".parse().unwrap()]);
+
+        assert!(matches!(parse(args("run game.ch8")), Ok(Command::Run(_))));
+        assert!(parse(args("run --ipf")).is_err());
+        assert!(parse(args("run --bogus game.ch8")).is_err());
+        assert!(parse(args("run")).is_err());
+        assert!(parse(args("run --quirk bogus=on game.ch8")).is_err());
+        assert!(parse(args("run --cheat 0x2F0=256 game.ch8")).is_err());
+    }
+
+    #[test]
+    fn test_flags_override_config() {
+        let rom = [0x00, 0xE0];
+        let config = Config::parse(&format!(
+            "ipf = 20\npalette = \"amber\"\n\n[rom.\"{}\"]\nipf = 30\nwrap_sprites = true",
+            sha1_hex(&rom)
+        ))
+        .unwrap();
+        let Command::Run(run) = parse(args("game.ch8 --palette lcd")).unwrap() else {
+            panic!("expected run");
+        };
+        let options = run.options(&config, &rom).unwrap();
+        assert_eq!(options.ipf, 30);
+        assert_eq!(options.palette, Some(Palette::LCD));
+        assert!(options.quirks().wrap_sprites);
+        assert_eq!(run.options(&config, &[0]).unwrap().ipf, 20);
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        assert!(matches!(
+            parse(args("test rom.ch8 --frames 10 --expect out.pbm --update")),
+            Ok(Command::Test {
+                update: true,
+                expect: Some(_),
+                ..
+            })
+        ));
+        assert!(parse(args("test rom.ch8 --expect out.png")).is_err());
+        assert!(matches!(
+            parse(args("asm game.asm")),
+            Ok(Command::Asm { output, .. }) if output == Path::new("game.ch8")
+        ));
+        assert!(matches!(
+            parse(args("disasm a.ch8")),
+            Ok(Command::Disasm { coverage: None, .. })
+        ));
+        assert!(matches!(parse(args("info --help")), Ok(Command::Help(_))));
+        assert!(matches!(parse(Vec::new()), Ok(Command::Help(_))));
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+    chip8::Chip8,
+    error::{Error, Result},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Register(usize),
+    Index,
+    Delay,
+    Sound,
+    /// Call stack depth.
+    Stack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Le,
+    Ge,
+    Lt,
+    Gt,
+}
+
+/// Longer symbols first so `<=` isn't read as `<`.
+const COMPARISONS: &[(&str, Comparison)] = &[
+    ("==", Comparison::Eq),
+    ("!=", Comparison::Ne),
+    ("<=", Comparison::Le),
+    (">=", Comparison::Ge),
+    ("<", Comparison::Lt),
+    (">", Comparison::Gt),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Term {
+    Compare(Operand, Comparison, u16),
+    /// `I` within `start..end`.
+    IndexIn(u16, u16),
+    /// The next opcode, masked, equals the value.
+    Opcode {
+        mask: u16,
+        value: u16,
+    },
+}
+
+/// A debugger condition on the machine state before an instruction: terms
+/// joined by `&&`, such as `V3 == 0x10`, `I in 0x300..0x310`, `stack > 2`,
+/// `op 00EE && stack == 0`, `draw` or `sound`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    text: String,
+    terms: Vec<Term>,
+}
+
+impl Condition {
+    pub fn holds(&self, chip: &Chip8) -> bool {
+        self.terms.iter().all(|term| match *term {
+            Term::Compare(operand, comparison, n) => {
+                let value = match operand {
+                    Operand::Register(x) => chip.registers()[x] as u16,
+                    Operand::Index => chip.index(),
+                    Operand::Delay => chip.timers().0 as u16,
+                    Operand::Sound => chip.timers().1 as u16,
+                    Operand::Stack => chip.call_stack().len() as u16,
+                };
+                match comparison {
+                    Comparison::Eq => value == n,
+                    Comparison::Ne => value != n,
+                    Comparison::Le => value <= n,
+                    Comparison::Ge => value >= n,
+                    Comparison::Lt => value < n,
+                    Comparison::Gt => value > n,
+                }
+            }
+            Term::IndexIn(start, end) => (start..end).contains(&chip.index()),
+            Term::Opcode { mask, value } => chip
+                .next_opcode()
+                .is_ok_and(|opcode| opcode.inner() & mask == value),
+        })
+    }
+}
+
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let terms = s.split("&&").map(parse_term).collect::<Result<_>>()?;
+        Ok(Self {
+            text: s.trim().to_string(),
+            terms,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn parse_term(term: &str) -> Result<Term> {
+    let term = term.trim().to_ascii_uppercase();
+    match term.as_str() {
+        "DRAW" => return parse_pattern("DXYN"),
+        "SOUND" => return Ok(Term::Compare(Operand::Sound, Comparison::Gt, 0)),
+        _ => {}
+    }
+    if let Some(pattern) = term.strip_prefix("OP ") {
+        return parse_pattern(pattern.trim());
+    }
+    if let Some(range) = term.strip_prefix("I IN ") {
+        let (start, end) = range
+            .split_once("..")
+            .ok_or_else(|| Error::Unknown(format!("expected start..end: {range}")))?;
+        return Ok(Term::IndexIn(
+            parse_number(start.trim())?,
+            parse_number(end.trim())?,
+        ));
+    }
+    let (symbol, comparison) = COMPARISONS
+        .iter()
+        .find(|(symbol, _)| term.contains(symbol))
+        .ok_or_else(|| Error::Unknown(format!("bad condition: {term}")))?;
+    let (operand, value) = term.split_once(symbol).unwrap_or_default();
+    let operand = match operand.trim() {
+        "I" => Operand::Index,
+        "DT" => Operand::Delay,
+        "ST" => Operand::Sound,
+        "STACK" => Operand::Stack,
+        register => register
+            .strip_prefix('V')
+            .filter(|x| x.len() == 1)
+            .and_then(|x| usize::from_str_radix(x, 16).ok())
+            .map(Operand::Register)
+            .ok_or_else(|| Error::Unknown(format!("unknown value: {register}")))?,
+    };
+    Ok(Term::Compare(
+        operand,
+        *comparison,
+        parse_number(value.trim())?,
+    ))
+}
+
+/// An opcode pattern like `00EE` or `8XY4`, where `X`, `Y` and `N` match any
+/// nibble.
+fn parse_pattern(pattern: &str) -> Result<Term> {
+    if pattern.len() != 4 {
+        return Err(Error::Unknown(format!("bad opcode pattern: {pattern}")));
+    }
+    let (mut mask, mut value) = (0, 0);
+    for c in pattern.chars() {
+        let (m, v) = match c {
+            'X' | 'Y' | 'N' => (0, 0),
+            _ => c
+                .to_digit(16)
+                .map(|digit| (0xF, digit as u16))
+                .ok_or_else(|| Error::Unknown(format!("bad opcode pattern: {pattern}")))?,
+        };
+        mask = mask << 4 | m;
+        value = value << 4 | v;
+    }
+    Ok(Term::Opcode { mask, value })
+}
+
+/// A decimal or `0x` hex number.
+pub fn parse_number(value: &str) -> Result<u16> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|e| Error::Unknown(format!("bad number {value}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Keypad;
+
+    #[test]
+    fn test_conditions() {
+        let mut chip = Chip8::default();
+        // 0x200: LD V3, 0x10; 0x202: LD I, 0x304; 0x204: RET
+        chip.load_rom(&[0x63, 0x10, 0xA3, 0x04, 0x00, 0xEE])
+            .unwrap();
+        let holds = |chip: &Chip8, text: &str| text.parse::<Condition>().unwrap().holds(chip);
+        assert!(holds(&chip, "v3 == 0"));
+        assert!(holds(&chip, "op 6XNN && stack == 0"));
+        chip.cycle(&Keypad::default()).unwrap();
+        chip.cycle(&Keypad::default()).unwrap();
+        assert!(holds(&chip, "V3==0x10 && I in 0x300..0x310"));
+        assert!(!holds(&chip, "I in 0x300..0x304"));
+        assert!(holds(&chip, "op 00EE && stack == 0"));
+        assert!(!holds(&chip, "draw"));
+        assert!(!holds(&chip, "sound"));
+        assert!("V3 = 1".parse::<Condition>().is_err());
+        assert!("op 0GEE".parse::<Condition>().is_err());
+    }
+}
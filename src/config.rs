@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    cli::RunOptions,
+    error::{Error, Result},
+    toml::{self, Entry, Value},
+};
+
+/// Settings from the config file, kept as `--flag value` pairs so they go
+/// through the same parsing as the command line. Top-level keys are global
+/// defaults; `[rom."<sha1>"]` tables override them for one ROM.
+#[derive(Debug, Default)]
+pub struct Config {
+    defaults: Vec<(String, String)>,
+    roms: HashMap<String, Vec<(String, String)>>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/chip8`, falling back to `~/.config`.
+    pub fn dir() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(base.join("chip8"))
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        Self::dir().map(|dir| dir.join("config.toml"))
+    }
+
+    /// Loads `path`, or the default path if there is a file there.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path().filter(|path| path.exists()) {
+                Some(path) => path,
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| Error::Io(format!("failed to read {}: {e}", path.display())))?;
+        Self::parse(&text).map_err(|e| Error::Unknown(format!("{}: {e}", path.display())))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config = Self::default();
+        let mut scratch = RunOptions::default();
+        for entry in toml::parse(text)? {
+            let at_line = |e: Error| Error::Unknown(format!("line {}: {e}", entry.line));
+            let Some((flag, value)) = flag(&entry).map_err(at_line)? else {
+                continue;
+            };
+            // Catch bad keys and values here, where the line is known
+            scratch.apply(&flag, &value).map_err(at_line)?;
+            let flags = match entry.table.as_slice() {
+                [] => &mut config.defaults,
+                [rom, hash] if rom == "rom" => config.roms.entry(hash.to_lowercase()).or_default(),
+                table => {
+                    return Err(at_line(Error::Unknown(format!(
+                        "unknown table: [{}]",
+                        table.join(".")
+                    ))));
+                }
+            };
+            flags.push((flag, value));
+        }
+        Ok(config)
+    }
+
+    /// Flags for the ROM with SHA-1 `hash`: the global defaults followed by
+    /// the ROM's own overrides.
+    pub fn flags_for<'a>(&'a self, hash: &str) -> impl Iterator<Item = &'a (String, String)> {
+        self.defaults
+            .iter()
+            .chain(self.roms.get(hash).into_iter().flatten())
+    }
+}
+
+/// The command line flag a config entry stands for. `false` switches are
+/// dropped.
+pub fn flag(entry: &Entry) -> Result<Option<(String, String)>> {
+    let key = entry.key.replace('_', "-");
+    match (key.as_str(), &entry.value) {
+        ("speed", value) => Ok(Some(("--ipf".to_string(), value.to_string()))),
+        ("display-wait" | "wrap-sprites", Value::Boolean(on)) => Ok(Some((
+            "--quirk".to_string(),
+            // Quirks go by their underscored names
+            format!(
+                "{}={}",
+                key.replace('-', "_"),
+                if *on { "on" } else { "off" }
+            ),
+        ))),
+        ("display-wait" | "wrap-sprites", _) => Err(Error::Unknown(format!(
+            "{} must be true or false",
+            entry.key
+        ))),
+        (_, Value::Boolean(true)) => Ok(Some((format!("--{key}"), String::new()))),
+        (_, Value::Boolean(false)) => Ok(None),
+        (_, value) => Ok(Some((format!("--{key}"), value.to_string()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rom_overrides() {
+        let config = Config::parse(
+            r#"
+            speed = 20
+            palette = "amber"
+
+            [rom."ABCDEF"]
+            platform = "xochip"
+            keymap = "0123456789abcdef"
+            display_wait = true
+            wrap-sprites = false
+            "#,
+        )
+        .unwrap();
+        let flags = |hash| config.flags_for(hash).cloned().collect::<Vec<_>>();
+        let pair = |flag: &str, value: &str| (flag.to_string(), value.to_string());
+        assert_eq!(
+            flags("other"),
+            [pair("--ipf", "20"), pair("--palette", "amber")]
+        );
+        assert_eq!(
+            flags("abcdef"),
+            [
+                pair("--ipf", "20"),
+                pair("--palette", "amber"),
+                pair("--platform", "xochip"),
+                pair("--keymap", "0123456789abcdef"),
+                pair("--quirk", "display_wait=on"),
+                pair("--quirk", "wrap_sprites=off"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let error = Config::parse("ipf = 10\nbogus = 1").unwrap_err();
+        assert!(error.to_string().starts_with("line 2:"));
+        assert!(Config::parse("[other]\nipf = 1").is_err());
+        assert!(Config::parse("palette = \"nope\"").is_err());
+    }
+}
@@ -0,0 +1,650 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    cheats::{Cheat, RamSearch, Target},
+    chip8::Chip8,
+    condition::{Condition, parse_number},
+    disasm::listing_line,
+    error::{Error, Result},
+    input::Keypad,
+    memory::Access,
+    memview::{hex_view, sprite_preview},
+    runner::Runner,
+};
+
+/// What the frontend should do after a debugger command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebuggerAction {
+    Stay,
+    Resume,
+    Quit,
+}
+
+const HELP: &[&str] = &[
+    "c, continue                  resume execution",
+    "s, step [n]                  execute n instructions (default 1)",
+    "bs, back [n]                 undo n instructions (default 1)",
+    "rc, reverse-continue         run backwards to the previous breakpoint",
+    "b, break <addr> [if <cond>]  set a breakpoint",
+    "b, break if <cond>           break where <cond> becomes true",
+    "w, watch <addr>[-<end>] [r|w|rw]  break on memory access (default w)",
+    "d, delete <addr>|#<n>        remove a breakpoint, watchpoint or condition",
+    "l, list                      list breakpoints",
+    "m, mem [addr] [rows]         hex view at addr (default I): PC reversed,",
+    "                             I underlined, font dim, recent writes yellow",
+    "sp, sprite [n]               the n bytes at I as DXYN draws them",
+    "poke <addr> <byte>...        write bytes to memory",
+    "freeze <addr|Vx> [value]     hold a value every frame, saved for this ROM",
+    "unfreeze <addr|Vx>           release a frozen value",
+    "cheats                       list frozen values",
+    "save <file>                  write the whole machine state to a file",
+    "load <file>                  restore a state written by save",
+    "search [= n|changed|unchanged|inc|dec]",
+    "                             narrow down addresses, or start over",
+    "q, quit                      exit the emulator",
+    "conditions: V3 == 0x10, I in 0x300..0x310, DT/ST/stack < n,",
+    "  op 00EE (X, Y, N match anything), draw, sound; join with &&",
+];
+
+/// Breaks on memory accesses to `start..=end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Watchpoint {
+    start: u16,
+    end: u16,
+    read: bool,
+    write: bool,
+}
+
+impl Watchpoint {
+    fn matches(&self, access: &Access) -> bool {
+        (self.start..=self.end).contains(&access.addr)
+            && if access.write { self.write } else { self.read }
+    }
+}
+
+impl std::fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match (self.read, self.write) {
+            (true, true) => "rw",
+            (true, false) => "r",
+            _ => "w",
+        };
+        match self.start == self.end {
+            true => write!(f, "watch 0x{:03X} {kind}", self.start),
+            false => write!(f, "watch 0x{:03X}-0x{:03X} {kind}", self.start, self.end),
+        }
+    }
+}
+
+/// Breakpoints, watchpoints and the paused state, driven by text commands
+/// while paused.
+#[derive(Debug, Default)]
+pub struct Debugger {
+    paused: bool,
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    /// Conditions checked before every instruction, with whether each held
+    /// last time. They break when they become true rather than while true.
+    conditions: Vec<(Condition, bool)>,
+    watchpoints: Vec<Watchpoint>,
+    /// The instruction `check` last let run, blamed for memory accesses.
+    last_pc: u16,
+    /// Addresses of the latest memory writes, oldest first.
+    recent_writes: Vec<u16>,
+    /// The RAM search in progress.
+    search: Option<RamSearch>,
+    /// Output of the last command, shown under the status lines.
+    output: Vec<String>,
+}
+
+impl Debugger {
+    pub fn new(paused: bool) -> Self {
+        Self {
+            paused,
+            ..Self::default()
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn set_breakpoint(&mut self, addr: u16, condition: Option<Condition>) {
+        self.breakpoints.insert(addr, condition);
+    }
+
+    /// Removes the breakpoint at `addr`, returning whether there was one.
+    pub fn clear_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    /// Called before each instruction; pauses on a breakpoint, a watched
+    /// access by the previous instruction or a condition becoming true, and
+    /// returns whether execution should stop.
+    pub fn check(&mut self, chip: &Chip8) -> bool {
+        let pc = chip.pc();
+        let mut reasons = self.watch_hits(chip, self.last_pc);
+        if let Some(condition) = self.breakpoints.get(&pc)
+            && condition.as_ref().is_none_or(|c| c.holds(chip))
+        {
+            reasons.push(format!("breakpoint at 0x{pc:03X}"));
+        }
+        for (i, (condition, held)) in self.conditions.iter_mut().enumerate() {
+            let holds = condition.holds(chip);
+            if holds && !*held {
+                reasons.push(format!("#{} {condition} at 0x{pc:03X}", i + 1));
+            }
+            *held = holds;
+        }
+        if !self.paused && !reasons.is_empty() {
+            self.paused = true;
+            self.output = reasons;
+        }
+        self.last_pc = pc;
+        self.paused
+    }
+
+    /// Drains the memory accesses made by the instruction at `pc`, noting
+    /// writes and describing accesses that hit a watchpoint.
+    fn watch_hits(&mut self, chip: &Chip8, pc: u16) -> Vec<String> {
+        let accesses = chip.take_accesses();
+        self.note_writes(accesses.iter().filter(|a| a.write).map(|a| a.addr));
+        accesses
+            .iter()
+            .filter(|access| self.watchpoints.iter().any(|w| w.matches(access)))
+            .map(|access| {
+                let kind = if access.write { "write" } else { "read" };
+                format!(
+                    "{kind} 0x{:03X} = 0x{:02X} at 0x{pc:03X}",
+                    access.addr, access.value
+                )
+            })
+            .collect()
+    }
+
+    fn note_writes(&mut self, addrs: impl IntoIterator<Item = u16>) {
+        const RECENT_WRITES: usize = 32;
+        self.recent_writes.extend(addrs);
+        let excess = self.recent_writes.len().saturating_sub(RECENT_WRITES);
+        self.recent_writes.drain(..excess);
+    }
+
+    /// Steps one instruction, returning any watchpoint hits.
+    fn step(&mut self, runner: &mut Runner, keypad: &Keypad) -> Result<Vec<String>> {
+        let pc = runner.chip.pc();
+        runner.chip.take_accesses();
+        runner.step(keypad)?;
+        Ok(self.watch_hits(&runner.chip, pc))
+    }
+
+    /// Undoes one instruction, or a snapshot's worth once past the fine
+    /// history, and resyncs the checks that compare against the last state.
+    fn step_back(&mut self, runner: &mut Runner) -> Option<usize> {
+        let steps = runner.step_back()?;
+        runner.chip.take_accesses();
+        self.last_pc = runner.chip.pc();
+        for (condition, held) in &mut self.conditions {
+            *held = condition.holds(&runner.chip);
+        }
+        Some(steps)
+    }
+
+    /// Runs one command line. Errors, including faults while stepping, are
+    /// reported in the panel rather than ending the session.
+    pub fn execute(&mut self, line: &str, runner: &mut Runner, keypad: &Keypad) -> DebuggerAction {
+        self.output.clear();
+        let action = self.command(line, runner, keypad).unwrap_or_else(|e| {
+            self.output.push(e.to_string());
+            DebuggerAction::Stay
+        });
+        runner.log_accesses(!self.watchpoints.is_empty());
+        action
+    }
+
+    fn command(
+        &mut self,
+        line: &str,
+        runner: &mut Runner,
+        keypad: &Keypad,
+    ) -> Result<DebuggerAction> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let argument = rest.split_whitespace().next();
+        match command {
+            "c" | "continue" => {
+                // Step off the current breakpoint before resuming, stopping
+                // again if that instruction hits a watchpoint
+                self.output = self.step(runner, keypad)?;
+                if self.output.is_empty() {
+                    self.paused = false;
+                    self.last_pc = runner.chip.pc();
+                    return Ok(DebuggerAction::Resume);
+                }
+            }
+            "s" | "step" => {
+                let count = argument.map_or(Ok(1), parse_number)?;
+                for _ in 0..count {
+                    let hits = self.step(runner, keypad)?;
+                    self.output.extend(hits);
+                }
+            }
+            "bs" | "back" => {
+                let count = argument.map_or(Ok(1), parse_number)? as usize;
+                let mut undone = 0;
+                while undone < count {
+                    let Some(steps) = self.step_back(runner) else {
+                        self.output.push("start of history".to_string());
+                        break;
+                    };
+                    undone += steps;
+                }
+                if undone > count {
+                    self.output
+                        .push(format!("back {undone} instructions to a snapshot"));
+                }
+            }
+            "rc" | "reverse-continue" => {
+                let mut undone = 0;
+                loop {
+                    let Some(steps) = self.step_back(runner) else {
+                        self.output.push("start of history".to_string());
+                        break;
+                    };
+                    undone += steps;
+                    let pc = runner.chip.pc();
+                    if let Some(condition) = self.breakpoints.get(&pc)
+                        && condition.as_ref().is_none_or(|c| c.holds(&runner.chip))
+                    {
+                        self.output.push(format!("breakpoint at 0x{pc:03X}"));
+                        break;
+                    }
+                }
+                self.output.push(format!("back {undone} instructions"));
+            }
+            "b" | "break" => {
+                let (addr, condition) = match rest.strip_prefix("if ") {
+                    Some(condition) => (None, Some(condition)),
+                    None => match rest.split_once(" if ") {
+                        Some((addr, condition)) => (Some(addr), Some(condition)),
+                        None => (argument, None),
+                    },
+                };
+                let condition = condition.map(str::parse::<Condition>).transpose()?;
+                match (addr, condition) {
+                    (None, Some(condition)) => {
+                        self.output.push(format!(
+                            "#{} break if {condition}",
+                            self.conditions.len() + 1
+                        ));
+                        let held = condition.holds(&runner.chip);
+                        self.conditions.push((condition, held));
+                    }
+                    (addr, condition) => {
+                        let addr = parse_address(addr)?;
+                        self.output.push(format!("breakpoint set at 0x{addr:03X}"));
+                        self.set_breakpoint(addr, condition);
+                    }
+                }
+            }
+            "w" | "watch" => {
+                let (start, end) = match argument.and_then(|range| range.split_once('-')) {
+                    Some((start, end)) => (parse_address(Some(start))?, parse_address(Some(end))?),
+                    None => {
+                        let addr = parse_address(argument)?;
+                        (addr, addr)
+                    }
+                };
+                let (read, write) = match rest.split_whitespace().nth(1) {
+                    None | Some("w") => (false, true),
+                    Some("r") => (true, false),
+                    Some("rw") => (true, true),
+                    Some(other) => {
+                        return Err(Error::Unknown(format!("expected r, w or rw: {other}")));
+                    }
+                };
+                let watchpoint = Watchpoint {
+                    start: start.min(end),
+                    end: start.max(end),
+                    read,
+                    write,
+                };
+                self.output.push(format!("{watchpoint} set"));
+                self.watchpoints.push(watchpoint);
+            }
+            "d" | "delete" => match argument.and_then(|arg| arg.strip_prefix('#')) {
+                Some(number) => {
+                    let index = parse_number(number)? as usize;
+                    if index == 0 || index > self.conditions.len() {
+                        return Err(Error::Unknown(format!("no condition #{number}")));
+                    }
+                    self.conditions.remove(index - 1);
+                }
+                None => {
+                    let addr = parse_address(argument)?;
+                    let watchpoints = self.watchpoints.len();
+                    self.watchpoints.retain(|w| w.start != addr);
+                    if !self.clear_breakpoint(addr) && self.watchpoints.len() == watchpoints {
+                        self.output.push(format!("no breakpoint at 0x{addr:03X}"));
+                    }
+                }
+            },
+            "l" | "list" => {
+                let breakpoints =
+                    self.breakpoints
+                        .iter()
+                        .map(|(addr, condition)| match condition {
+                            Some(condition) => format!("0x{addr:03X} if {condition}"),
+                            None => format!("0x{addr:03X}"),
+                        });
+                let watchpoints = self.watchpoints.iter().map(Watchpoint::to_string);
+                let conditions = self
+                    .conditions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (condition, _))| format!("#{} if {condition}", i + 1));
+                self.output = breakpoints.chain(watchpoints).chain(conditions).collect();
+            }
+            "m" | "mem" => {
+                let mut args = rest.split_whitespace();
+                let addr = match args.next() {
+                    Some(addr) => parse_address(Some(addr))?,
+                    None => runner.chip.index(),
+                };
+                let rows = args.next().map_or(Ok(4), parse_number)?;
+                self.output = hex_view(&runner.chip, addr, rows as usize, &self.recent_writes);
+            }
+            "sp" | "sprite" => {
+                // Default to the height of the sprite about to be drawn
+                let n = match (argument, runner.chip.next_opcode()) {
+                    (Some(n), _) => parse_number(n)?,
+                    (None, Ok(opcode)) if opcode.code() == 0xD => opcode.n() as u16,
+                    (None, _) => 5,
+                };
+                self.output = sprite_preview(&runner.chip, n as usize)?;
+            }
+            "poke" => {
+                let mut args = rest.split_whitespace();
+                let addr = parse_address(args.next())?;
+                let bytes = args
+                    .map(|byte| match parse_number(byte)? {
+                        value @ 0..=0xFF => Ok(value as u8),
+                        _ => Err(Error::Unknown(format!("not a byte: {byte}"))),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if bytes.is_empty() {
+                    return Err(Error::Unknown("nothing to poke".to_string()));
+                }
+                runner.chip.write_memory(addr, &bytes)?;
+                self.note_writes((addr..).take(bytes.len()));
+                self.output = hex_view(&runner.chip, addr, 1, &self.recent_writes);
+            }
+            "freeze" => {
+                let mut args = rest.split_whitespace();
+                let target = args
+                    .next()
+                    .ok_or_else(|| Error::Unknown("missing address".to_string()))?
+                    .parse::<Target>()?;
+                let cheat = match args.next() {
+                    Some(value) => format!("{target}={value}").parse::<Cheat>()?,
+                    // Hold whatever is there now
+                    None => Cheat {
+                        target,
+                        value: match target {
+                            Target::Memory(addr) => runner.chip.read_memory(addr, 1)?[0],
+                            Target::Register(x) => runner.chip.registers()[x as usize],
+                        },
+                    },
+                };
+                let cheats = runner.cheats_mut();
+                cheats.set(cheat);
+                cheats.save()?;
+                self.output.push(format!("froze {cheat}"));
+            }
+            "unfreeze" => {
+                let target = argument
+                    .ok_or_else(|| Error::Unknown("missing address".to_string()))?
+                    .parse::<Target>()?;
+                let cheats = runner.cheats_mut();
+                if !cheats.remove(target) {
+                    return Err(Error::Unknown(format!("{target} isn't frozen")));
+                }
+                cheats.save()?;
+            }
+            "cheats" => {
+                self.output = runner.cheats().iter().map(Cheat::to_string).collect();
+            }
+            "save" => {
+                let path = argument.ok_or_else(|| Error::Unknown("missing file".to_string()))?;
+                std::fs::write(path, runner.chip.save_state())?;
+                self.output.push(format!("saved {path}"));
+            }
+            "load" => {
+                let path = argument.ok_or_else(|| Error::Unknown("missing file".to_string()))?;
+                runner.load_state(&std::fs::read(path)?)?;
+                self.output.push(format!("loaded {path}"));
+            }
+            "search" => {
+                const SHOWN: usize = 8;
+                match rest {
+                    "" => self.search = Some(RamSearch::new(&runner.chip)),
+                    filter => {
+                        let filter = filter.parse()?;
+                        self.search
+                            .get_or_insert_with(|| RamSearch::new(&runner.chip))
+                            .filter(&runner.chip, filter);
+                    }
+                }
+                if let Some(search) = &self.search {
+                    self.output.push(format!("{} candidates", search.len()));
+                    if search.len() <= SHOWN {
+                        self.output.extend(
+                            search
+                                .candidates()
+                                .map(|(addr, value)| format!("0x{addr:03X} = {value}")),
+                        );
+                    }
+                }
+            }
+            "q" | "quit" => return Ok(DebuggerAction::Quit),
+            "" => {}
+            _ => self.output = HELP.iter().map(|line| line.to_string()).collect(),
+        }
+        Ok(DebuggerAction::Stay)
+    }
+
+    /// Status lines for the panel: the next instruction, registers, and the
+    /// output of the last command.
+    pub fn panel(&self, chip: &Chip8) -> Vec<String> {
+        let next = chip.next_opcode().map_or_else(
+            |e| format!("0x{:03X}  {e}", chip.pc()),
+            |opcode| listing_line(chip.pc(), opcode),
+        );
+        let registers = chip.registers();
+        let row = |range: std::ops::Range<usize>| {
+            range
+                .map(|i| format!("V{i:X}={:02X}", registers[i]))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let (delay, sound) = chip.timers();
+        let mut lines = vec![
+            format!("paused  {next}"),
+            row(0..8),
+            row(8..16),
+            format!("I=0x{:03X} DT={delay:02X} ST={sound:02X}", chip.index()),
+        ];
+        lines.extend(self.output.iter().cloned());
+        lines
+    }
+}
+
+fn parse_address(value: Option<&str>) -> Result<u16> {
+    let value = value.ok_or_else(|| Error::Unknown("missing address".to_string()))?;
+    // Addresses are hex whether or not they carry the prefix
+    let hex = value.strip_prefix("0x").unwrap_or(value);
+    u16::from_str_radix(hex, 16).map_err(|e| Error::Unknown(format!("bad address {value}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakpoint_and_continue() {
+        let mut chip = Chip8::default();
+        // 0x200: LD V0, 0x01; 0x202: LD V1, 0x02; 0x204: JP 0x204
+        chip.load_rom(&[0x60, 0x01, 0x61, 0x02, 0x12, 0x04])
+            .unwrap();
+        let mut runner = Runner::new(chip, 10);
+        let keypad = Keypad::default();
+        let mut debugger = Debugger::default();
+
+        debugger.execute("b 202", &mut runner, &keypad);
+        runner.frame(&keypad, &mut debugger).unwrap();
+        assert!(debugger.paused());
+        assert_eq!(runner.chip.pc(), 0x202);
+
+        let action = debugger.execute("c", &mut runner, &keypad);
+        assert_eq!(action, DebuggerAction::Resume);
+        runner.frame(&keypad, &mut debugger).unwrap();
+        assert!(!debugger.paused());
+        assert_eq!(runner.chip.registers()[..2], [0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_step() {
+        let mut chip = Chip8::default();
+        chip.load_rom(&[0x60, 0x01, 0x61, 0x02]).unwrap();
+        let mut runner = Runner::new(chip, 10);
+        let mut debugger = Debugger::new(true);
+        debugger.execute("s 2", &mut runner, &Keypad::default());
+        assert_eq!(runner.chip.pc(), 0x204);
+        assert!(debugger.panel(&runner.chip)[0].starts_with("paused  0x204"));
+    }
+
+    #[test]
+    fn test_watchpoints_and_conditions() {
+        // 0x200: LD I, 0x300; 0x202: LD V0, 0x05; 0x204: LD [I], V0;
+        // 0x206: JP 0x206
+        let rom = [0xA3, 0x00, 0x60, 0x05, 0xF0, 0x55, 0x12, 0x06];
+        let keypad = Keypad::default();
+        let new_runner = || {
+            let mut chip = Chip8::default();
+            chip.load_rom(&rom).unwrap();
+            Runner::new(chip, 10)
+        };
+
+        let (mut runner, mut debugger) = (new_runner(), Debugger::default());
+        debugger.execute("watch 0x2FF-0x301", &mut runner, &keypad);
+        runner.frame(&keypad, &mut debugger).unwrap();
+        assert_eq!(runner.chip.pc(), 0x206);
+        assert!(debugger.panel(&runner.chip)[4].starts_with("write 0x300 = 0x05 at 0x204"));
+
+        let (mut runner, mut debugger) = (new_runner(), Debugger::default());
+        debugger.execute("b if V0 == 5 && I in 0x300..0x301", &mut runner, &keypad);
+        runner.frame(&keypad, &mut debugger).unwrap();
+        assert_eq!(runner.chip.pc(), 0x204);
+        // Still true after continuing, so it doesn't break again
+        debugger.execute("c", &mut runner, &keypad);
+        runner.frame(&keypad, &mut debugger).unwrap();
+        assert!(!debugger.paused());
+
+        let (mut runner, mut debugger) = (new_runner(), Debugger::default());
+        debugger.execute("b 206 if V0 != 5", &mut runner, &keypad);
+        debugger.execute("b 204 if op FX55", &mut runner, &keypad);
+        runner.frame(&keypad, &mut debugger).unwrap();
+        assert_eq!(runner.chip.pc(), 0x204);
+        debugger.execute("l", &mut runner, &keypad);
+        assert_eq!(
+            debugger.panel(&runner.chip)[4..],
+            ["0x204 if op FX55", "0x206 if V0 != 5"]
+        );
+    }
+
+    #[test]
+    fn test_back_and_reverse_continue() {
+        let mut chip = Chip8::default();
+        // 0x200: LD V0, 0x01; 0x202: ADD V0, 0x01; 0x204: ADD V0, 0x01;
+        // 0x206: JP 0x202
+        chip.load_rom(&[0x60, 0x01, 0x70, 0x01, 0x70, 0x01, 0x12, 0x02])
+            .unwrap();
+        let mut runner = Runner::new(chip, 10);
+        runner.start_history();
+        let keypad = Keypad::default();
+        let mut debugger = Debugger::new(true);
+        debugger.execute("s 6", &mut runner, &keypad);
+        assert_eq!((runner.chip.pc(), runner.chip.registers()[0]), (0x206, 5));
+
+        debugger.execute("back 2", &mut runner, &keypad);
+        assert_eq!((runner.chip.pc(), runner.chip.registers()[0]), (0x202, 3));
+        debugger.execute("b 204", &mut runner, &keypad);
+        debugger.execute("rc", &mut runner, &keypad);
+        assert_eq!((runner.chip.pc(), runner.chip.registers()[0]), (0x204, 2));
+        assert_eq!(
+            debugger.output,
+            ["breakpoint at 0x204", "back 2 instructions"]
+        );
+        debugger.execute("rc", &mut runner, &keypad);
+        assert_eq!(runner.chip.pc(), 0x200);
+        assert_eq!(debugger.output, ["start of history", "back 2 instructions"]);
+        debugger.execute("s", &mut runner, &keypad);
+        assert_eq!(runner.chip.registers()[0], 1);
+    }
+
+    #[test]
+    fn test_search_and_freeze() {
+        let mut chip = Chip8::default();
+        // 0x200: LD V0, 0x03; 0x202: LD I, 0x300; 0x204: LD [I], V0;
+        // 0x206: ADD V0, 0xFF; 0x208: JP 0x204
+        chip.load_rom(&[0x60, 0x03, 0xA3, 0x00, 0xF0, 0x55, 0x70, 0xFF, 0x12, 0x04])
+            .unwrap();
+        let mut runner = Runner::new(chip, 10);
+        let keypad = Keypad::default();
+        let mut debugger = Debugger::new(true);
+        debugger.execute("s 3", &mut runner, &keypad);
+        debugger.execute("search = 3", &mut runner, &keypad);
+        debugger.execute("s 3", &mut runner, &keypad);
+        debugger.execute("search dec", &mut runner, &keypad);
+        assert_eq!(debugger.output, ["1 candidates", "0x300 = 2"]);
+
+        debugger.execute("freeze 300 9", &mut runner, &keypad);
+        debugger.execute("freeze V0", &mut runner, &keypad);
+        debugger.execute("cheats", &mut runner, &keypad);
+        assert_eq!(debugger.output, ["0x300=9", "V0=2"]);
+        debugger.execute("unfreeze 300", &mut runner, &keypad);
+        debugger.execute("cheats", &mut runner, &keypad);
+        assert_eq!(debugger.output, ["V0=2"]);
+        debugger.execute("unfreeze 300", &mut runner, &keypad);
+        assert_eq!(debugger.output, ["0x300 isn't frozen"]);
+    }
+
+    #[test]
+    fn test_poke_and_mem() {
+        let mut chip = Chip8::default();
+        chip.load_rom(&[0xA3, 0x00, 0xD0, 0x12]).unwrap();
+        let mut runner = Runner::new(chip, 10);
+        let keypad = Keypad::default();
+        let mut debugger = Debugger::new(true);
+        debugger.execute("poke 300 0xFF 0x81", &mut runner, &keypad);
+        assert_eq!(runner.chip.read_memory(0x300, 2).unwrap(), [0xFF, 0x81]);
+        assert!(debugger.output[0].starts_with("0x300  "));
+        debugger.execute("s", &mut runner, &keypad);
+        debugger.execute("sprite", &mut runner, &keypad);
+        assert_eq!(
+            debugger.output,
+            ["0x300  FF  ████████", "0x301  81  █······█"]
+        );
+        debugger.execute("mem 200 2", &mut runner, &keypad);
+        assert_eq!(debugger.output.len(), 2);
+        debugger.execute("poke 300 256", &mut runner, &keypad);
+        assert!(debugger.output[0].starts_with("not a byte"));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{
+    analysis::Analysis,
+    memory::{Memory, OpCode},
+};
+
+/// Mnemonic for a single instruction, `DW` for words that don't decode.
+pub fn disassemble(opcode: OpCode) -> String {
+    let (x, y, n, nn, nnn) = (
+        opcode.x(),
+        opcode.y(),
+        opcode.n(),
+        opcode.nn(),
+        opcode.nnn(),
+    );
+    match (opcode.code(), x, y, n) {
+        (0x0, 0x0, 0xE, 0x0) => "CLS".to_string(),
+        (0x0, 0x0, 0xE, 0xE) => "RET".to_string(),
+        (0x0, ..) => format!("SYS 0x{nnn:03X}"),
+        (0x1, ..) => format!("JP 0x{nnn:03X}"),
+        (0x2, ..) => format!("CALL 0x{nnn:03X}"),
+        (0x3, ..) => format!("SE V{x:X}, 0x{nn:02X}"),
+        (0x4, ..) => format!("SNE V{x:X}, 0x{nn:02X}"),
+        (0x5, _, _, 0x0) => format!("SE V{x:X}, V{y:X}"),
+        (0x6, ..) => format!("LD V{x:X}, 0x{nn:02X}"),
+        (0x7, ..) => format!("ADD V{x:X}, 0x{nn:02X}"),
+        (0x8, _, _, 0x0) => format!("LD V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x1) => format!("OR V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x2) => format!("AND V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x3) => format!("XOR V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x4) => format!("ADD V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x5) => format!("SUB V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x6) => format!("SHR V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x7) => format!("SUBN V{x:X}, V{y:X}"),
+        (0x8, _, _, 0xE) => format!("SHL V{x:X}, V{y:X}"),
+        (0x9, _, _, 0x0) => format!("SNE V{x:X}, V{y:X}"),
+        (0xA, ..) => format!("LD I, 0x{nnn:03X}"),
+        (0xB, ..) => format!("JP V0, 0x{nnn:03X}"),
+        (0xC, ..) => format!("RND V{x:X}, 0x{nn:02X}"),
+        (0xD, ..) => format!("DRW V{x:X}, V{y:X}, {n}"),
+        (0xE, _, 0x9, 0xE) => format!("SKP V{x:X}"),
+        (0xE, _, 0xA, 0x1) => format!("SKNP V{x:X}"),
+        (0xF, 0x0, 0x0, 0x2) => "AUDIO".to_string(),
+        (0xF, _, 0x0, 0x7) => format!("LD V{x:X}, DT"),
+        (0xF, _, 0x0, 0xA) => format!("LD V{x:X}, K"),
+        (0xF, _, 0x1, 0x5) => format!("LD DT, V{x:X}"),
+        (0xF, _, 0x1, 0x8) => format!("LD ST, V{x:X}"),
+        (0xF, _, 0x1, 0xE) => format!("ADD I, V{x:X}"),
+        (0xF, _, 0x2, 0x9) => format!("LD F, V{x:X}"),
+        (0xF, _, 0x3, 0x3) => format!("LD B, V{x:X}"),
+        (0xF, _, 0x3, 0xA) => format!("PITCH V{x:X}"),
+        (0xF, _, 0x5, 0x5) => format!("LD [I], V{x:X}"),
+        (0xF, _, 0x6, 0x5) => format!("LD V{x:X}, [I]"),
+        _ => format!("DW 0x{:04X}", opcode.inner()),
+    }
+}
+
+/// One listing line: address, raw word and mnemonic.
+pub fn listing_line(addr: u16, opcode: OpCode) -> String {
+    format!(
+        "0x{addr:03X}  {:04X}  {}",
+        opcode.inner(),
+        disassemble(opcode)
+    )
+}
+
+/// The listing line for the instruction at `addr` in a program loaded at
+/// `Memory::PROGRAM_START`. XO-CHIP's 4 byte `F000 NNNN` is written as data
+/// so the listing still assembles.
+pub fn instruction_line(program: &[u8], addr: u16) -> String {
+    let offset = (addr - Memory::PROGRAM_START) as usize;
+    let word = |offset: usize| {
+        program
+            .get(offset..offset + 2)
+            .map_or(0, |w| u16::from_be_bytes([w[0], w[1]]))
+    };
+    match word(offset) {
+        0xF000 => {
+            let nnnn = word(offset + 2);
+            format!("0x{addr:03X}  F000  DW 0xF000, 0x{nnnn:04X}  ; LD I, LONG 0x{nnnn:04X}")
+        }
+        opcode => listing_line(addr, OpCode::from(opcode)),
+    }
+}
+
+/// Disassembly of a program loaded at `Memory::PROGRAM_START`. Instructions
+/// the analysis found reachable are decoded and everything else is shown as
+/// `DB` data, so code after data keeps its alignment.
+pub fn disassemble_rom(program: &[u8], analysis: &Analysis) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < program.len() {
+        let addr = Memory::PROGRAM_START + offset as u16;
+        if let Some(len) = analysis.instructions.get(&addr) {
+            lines.push(instruction_line(program, addr));
+            offset += *len as usize;
+            continue;
+        }
+        let pair = offset + 1 < program.len() && !analysis.instructions.contains_key(&(addr + 1));
+        let bytes = &program[offset..offset + if pair { 2 } else { 1 }];
+        let raw = bytes.iter().map(|b| format!("{b:02X}")).collect::<String>();
+        let values = bytes
+            .iter()
+            .map(|b| format!("0x{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(format!("0x{addr:03X}  {raw:<4}  DB {values}"));
+        offset += bytes.len();
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble() {
+        let cases = [
+            (0x00E0, "CLS"),
+            (0x00EE, "RET"),
+            (0x1228, "JP 0x228"),
+            (0x6A02, "LD VA, 0x02"),
+            (0x8AB4, "ADD VA, VB"),
+            (0xD01F, "DRW V0, V1, 15"),
+            (0xF355, "LD [I], V3"),
+            (0xF002, "AUDIO"),
+            (0x5AB1, "DW 0x5AB1"),
+        ];
+        for (opcode, text) in cases {
+            assert_eq!(disassemble(OpCode::from(opcode)), text);
+        }
+    }
+
+    #[test]
+    fn test_disassemble_rom() {
+        let lines = |program: &[u8]| disassemble_rom(program, &Analysis::new(program));
+        assert_eq!(
+            lines(&[0x00, 0xE0, 0x12]),
+            ["0x200  00E0  CLS", "0x202  12    DB 0x12"]
+        );
+        // Data before code at an odd address
+        assert_eq!(
+            lines(&[0x12, 0x03, 0xAB, 0x00, 0xEE, 0xF0, 0x90]),
+            [
+                "0x200  1203  JP 0x203",
+                "0x202  AB    DB 0xAB",
+                "0x203  00EE  RET",
+                "0x205  F090  DB 0xF0, 0x90",
+            ]
+        );
+    }
+}
@@ -1,12 +1,64 @@
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DisplayBuffer {
-    pub pixels: [[bool; Self::WIDTH]; Self::HEIGHT],
+    /// Palette index of each pixel, bit `n` set when it's lit on plane `n`.
+    pub pixels: [[u8; Self::WIDTH]; Self::HEIGHT],
+    dirty: Option<DirtyRect>,
+}
+
+/// Inclusive bounds of the pixels touched since the last `take_dirty`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl DirtyRect {
+    fn include(self, x: usize, y: usize) -> Self {
+        Self {
+            x0: self.x0.min(x),
+            y0: self.y0.min(y),
+            x1: self.x1.max(x),
+            y1: self.y1.max(y),
+        }
+    }
+}
+
+/// Anything a renderer can draw: a grid of palette indices with a brightness.
+pub trait PixelSource {
+    fn size(&self) -> (usize, usize);
+
+    /// Palette index of a pixel with one bit per bitplane, `0` when out of bounds.
+    fn color_index(&self, x: usize, y: usize) -> u8;
+
+    fn brightness(&self, x: usize, y: usize) -> u8 {
+        if self.color_index(x, y) != 0 {
+            u8::MAX
+        } else {
+            0
+        }
+    }
+}
+
+impl PixelSource for DisplayBuffer {
+    fn size(&self) -> (usize, usize) {
+        (Self::WIDTH, Self::HEIGHT)
+    }
+
+    fn color_index(&self, x: usize, y: usize) -> u8 {
+        self.pixels
+            .get(y)
+            .and_then(|row| row.get(x))
+            .map_or(0, |index| *index)
+    }
 }
 
 impl Default for DisplayBuffer {
     fn default() -> Self {
         Self {
-            pixels: [[false; Self::WIDTH]; Self::HEIGHT],
+            pixels: [[0; Self::WIDTH]; Self::HEIGHT],
+            dirty: Some(Self::FULL),
         }
     }
 }
@@ -14,13 +66,24 @@ impl Default for DisplayBuffer {
 impl DisplayBuffer {
     pub const WIDTH: usize = 64;
     pub const HEIGHT: usize = 32;
+    const FULL: DirtyRect = DirtyRect {
+        x0: 0,
+        y0: 0,
+        x1: Self::WIDTH - 1,
+        y1: Self::HEIGHT - 1,
+    };
 
-    pub fn clear(&mut self) -> Result<(), std::io::Error> {
-        self.pixels = [[false; Self::WIDTH]; Self::HEIGHT];
+    /// Clears the bitplanes set in `planes`, leaving the others.
+    pub fn clear(&mut self, planes: u8) -> Result<(), std::io::Error> {
+        for pixel in self.pixels.iter_mut().flatten() {
+            *pixel &= !planes;
+        }
+        self.dirty = Some(Self::FULL);
         Ok(())
     }
 
-    pub fn is_on<A: Into<usize>>(&self, x: A, y: A) -> Result<bool, String> {
+    /// A pixel's palette index, one bit per plane.
+    pub fn get<A: Into<usize>>(&self, x: A, y: A) -> Result<u8, String> {
         let x = x.into();
         let y = y.into();
         let row = self.pixels.get(y).ok_or("y overflow".to_string())?;
@@ -28,12 +91,33 @@ impl DisplayBuffer {
         Ok(*pixel)
     }
 
-    pub fn set<A: Into<usize>>(&mut self, x: A, y: A, state: bool) -> Result<(), String> {
+    /// Sets a pixel's palette index, one bit per plane.
+    pub fn set<A: Into<usize>>(&mut self, x: A, y: A, index: u8) -> Result<(), String> {
         let x = x.into();
         let y = y.into();
         let row = self.pixels.get_mut(y).ok_or("y overflow".to_string())?;
         let pixel = row.get_mut(x).ok_or("x overflow".to_string())?;
-        *pixel = state;
+        *pixel = index;
+        self.dirty = Some(match self.dirty {
+            Some(rect) => rect.include(x, y),
+            None => DirtyRect {
+                x0: x,
+                y0: y,
+                x1: x,
+                y1: y,
+            },
+        });
         Ok(())
     }
+
+    /// Marks the whole buffer for redrawing.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = Some(Self::FULL);
+    }
+
+    /// Returns the region changed since the previous call, `None` if nothing
+    /// was drawn or cleared in between.
+    pub fn take_dirty(&mut self) -> Option<DirtyRect> {
+        self.dirty.take()
+    }
 }
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+
+use crate::display::{DisplayBuffer, PixelSource};
+use crate::error::{Error, Result};
+
+/// Flicker reduction applied by the frontend before rendering.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlickerMode {
+    /// OR together the last N frames.
+    Blend(usize),
+    /// Pixels that turn off fade out, losing this fraction of brightness per frame.
+    /// Only the renderers with shading can show it.
+    Phosphor(f32),
+}
+
+/// A frame after flicker reduction, with a brightness for every pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadedFrame {
+    width: usize,
+    height: usize,
+    values: Vec<u8>,
+    brightness: Vec<u8>,
+}
+
+impl ShadedFrame {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            values: vec![0; width * height],
+            brightness: vec![0; width * height],
+        }
+    }
+}
+
+impl PixelSource for ShadedFrame {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn color_index(&self, x: usize, y: usize) -> u8 {
+        if x < self.width && y < self.height {
+            self.values[y * self.width + x]
+        } else {
+            0
+        }
+    }
+
+    fn brightness(&self, x: usize, y: usize) -> u8 {
+        if x < self.width && y < self.height {
+            self.brightness[y * self.width + x]
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FlickerFilter {
+    mode: FlickerMode,
+    history: VecDeque<Vec<u8>>,
+    intensity: Vec<f32>,
+    frame: ShadedFrame,
+}
+
+impl FlickerFilter {
+    /// Phosphor brightness below which a pixel counts as fully dark.
+    const DARK: f32 = 0.05;
+
+    pub fn new(mode: FlickerMode) -> Self {
+        let (width, height) = (DisplayBuffer::WIDTH, DisplayBuffer::HEIGHT);
+        Self {
+            mode,
+            history: VecDeque::new(),
+            intensity: vec![0.0; width * height],
+            frame: ShadedFrame::new(width, height),
+        }
+    }
+
+    /// Feeds the buffer as it stands at a frame boundary.
+    pub fn push(&mut self, display: &DisplayBuffer) {
+        let (width, height) = display.size();
+        let current = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| display.color_index(x, y))
+            .collect::<Vec<_>>();
+        match self.mode {
+            FlickerMode::Blend(frames) => {
+                self.history.push_back(current);
+                while self.history.len() > frames.max(1) {
+                    self.history.pop_front();
+                }
+                for i in 0..width * height {
+                    let value = self.history.iter().map(|frame| frame[i]).fold(0, u8::max);
+                    self.frame.values[i] = value;
+                    self.frame.brightness[i] = if value != 0 { u8::MAX } else { 0 };
+                }
+            }
+            FlickerMode::Phosphor(decay) => {
+                for (i, value) in current.into_iter().enumerate() {
+                    if value != 0 {
+                        self.intensity[i] = 1.0;
+                        self.frame.values[i] = value;
+                    } else {
+                        self.intensity[i] *= 1.0 - decay.clamp(0.0, 1.0);
+                        if self.intensity[i] < Self::DARK {
+                            self.intensity[i] = 0.0;
+                            self.frame.values[i] = 0;
+                        }
+                    }
+                    self.frame.brightness[i] = (self.intensity[i] * u8::MAX as f32) as u8;
+                }
+            }
+        }
+    }
+
+    /// Whether pushing the same buffer again would leave the output unchanged.
+    pub fn settled(&self) -> bool {
+        match self.mode {
+            FlickerMode::Blend(_) => self
+                .history
+                .iter()
+                .all(|frame| Some(frame) == self.history.back()),
+            FlickerMode::Phosphor(_) => self.intensity.iter().all(|i| *i == 0.0 || *i == 1.0),
+        }
+    }
+
+    pub fn frame(&self) -> &ShadedFrame {
+        &self.frame
+    }
+}
+
+impl std::str::FromStr for FlickerMode {
+    type Err = Error;
+
+    /// `blend:<frames>` or `phosphor:<decay>`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, arg) = s.split_once(':').unwrap_or((s, ""));
+        let bad_arg = |e: &dyn std::fmt::Display| Error::Unknown(format!("bad {name} value: {e}"));
+        match name {
+            "blend" if arg.is_empty() => Ok(FlickerMode::Blend(2)),
+            "blend" => Ok(FlickerMode::Blend(arg.parse().map_err(|e| bad_arg(&e))?)),
+            "phosphor" if arg.is_empty() => Ok(FlickerMode::Phosphor(0.5)),
+            "phosphor" => Ok(FlickerMode::Phosphor(arg.parse().map_err(|e| bad_arg(&e))?)),
+            _ => Err(Error::Unknown(format!("unknown flicker mode: {s}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend() {
+        let mut filter = FlickerFilter::new(FlickerMode::Blend(2));
+        let mut display = DisplayBuffer::default();
+        display.set(1usize, 1, 1).unwrap();
+        filter.push(&display);
+        display.set(1usize, 1, 0).unwrap();
+        filter.push(&display);
+        assert_eq!(filter.frame().color_index(1, 1), 1);
+        assert!(!filter.settled());
+        filter.push(&display);
+        assert_eq!(filter.frame().color_index(1, 1), 0);
+        assert!(filter.settled());
+    }
+
+    #[test]
+    fn test_phosphor() {
+        let mut filter = FlickerFilter::new(FlickerMode::Phosphor(0.5));
+        let mut display = DisplayBuffer::default();
+        display.set(1usize, 1, 1).unwrap();
+        filter.push(&display);
+        assert_eq!(filter.frame().brightness(1, 1), 255);
+        display.set(1usize, 1, 0).unwrap();
+        filter.push(&display);
+        assert_eq!(filter.frame().brightness(1, 1), 127);
+        assert_eq!(filter.frame().color_index(1, 1), 1);
+        for _ in 0..5 {
+            filter.push(&display);
+        }
+        assert_eq!(filter.frame().color_index(1, 1), 0);
+        assert!(filter.settled());
+    }
+}
@@ -0,0 +1,416 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::{
+    debugger::Debugger,
+    error::{Error, Result},
+    input::Keypad,
+    memory::Memory,
+    runner::Runner,
+};
+
+/// Register numbers and sizes in bytes as GDB sees them: V0-VF, then I, PC,
+/// SP (the call stack depth), DT and ST. Values are little-endian on the wire.
+const REGISTERS: &[(&str, usize)] = &[
+    ("v0", 1),
+    ("v1", 1),
+    ("v2", 1),
+    ("v3", 1),
+    ("v4", 1),
+    ("v5", 1),
+    ("v6", 1),
+    ("v7", 1),
+    ("v8", 1),
+    ("v9", 1),
+    ("va", 1),
+    ("vb", 1),
+    ("vc", 1),
+    ("vd", 1),
+    ("ve", 1),
+    ("vf", 1),
+    ("i", 2),
+    ("pc", 2),
+    ("sp", 1),
+    ("dt", 1),
+    ("st", 1),
+];
+
+const SIGINT: &str = "S02";
+const SIGILL: &str = "S04";
+const SIGTRAP: &str = "S05";
+
+/// A packet from the client, or the interrupt byte it sends outside of one.
+enum Packet {
+    Command(String),
+    Interrupt,
+}
+
+/// Serves one GDB remote serial protocol session on `listener`, with the
+/// machine stopped until the client continues it. Returns when the client
+/// detaches, kills or disconnects.
+pub fn serve(listener: &TcpListener, runner: &mut Runner) -> Result<()> {
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    let mut session = Session {
+        reader: BufReader::new(stream.try_clone()?),
+        stream,
+        debugger: Debugger::new(true),
+    };
+    while let Some(packet) = session.read_packet()? {
+        let Packet::Command(command) = packet else {
+            continue;
+        };
+        let reply = match command.as_str() {
+            "k" => return Ok(()),
+            "D" => {
+                session.send("OK")?;
+                return Ok(());
+            }
+            "c" => session.run(runner)?,
+            "s" => {
+                let stepped = runner.step(&Keypad::default());
+                // Nothing watches accesses here, so don't let them pile up
+                runner.chip.take_accesses();
+                match stepped {
+                    Ok(()) => SIGTRAP.to_string(),
+                    Err(_) => SIGILL.to_string(),
+                }
+            }
+            // Plain error numbers; error text needs negotiating first
+            _ => handle(&command, runner, &mut session.debugger)
+                .unwrap_or_else(|_| "E01".to_string()),
+        };
+        session.send(&reply)?;
+    }
+    Ok(())
+}
+
+/// Replies to the commands that don't run the machine.
+fn handle(command: &str, runner: &mut Runner, debugger: &mut Debugger) -> Result<String> {
+    let (kind, args) = command.split_at(command.len().min(1));
+    Ok(match kind {
+        "?" => SIGTRAP.to_string(),
+        "g" => (0..REGISTERS.len())
+            .map(|n| read_register(runner, n))
+            .collect(),
+        "G" => {
+            let mut values = args;
+            for (n, (_, size)) in REGISTERS.iter().enumerate() {
+                let (value, rest) = values.split_at(values.len().min(size * 2));
+                write_register(runner, n, value)?;
+                values = rest;
+            }
+            "OK".to_string()
+        }
+        "p" => read_register(runner, number(args)?),
+        "P" => {
+            let (n, value) = split(args, '=')?;
+            write_register(runner, number(n)?, value)?;
+            "OK".to_string()
+        }
+        "m" => {
+            let (addr, len) = split(args, ',')?;
+            hex(runner.chip.read_memory(address(addr)?, number(len)?)?)
+        }
+        "M" => {
+            let (range, data) = split(args, ':')?;
+            let (addr, _) = split(range, ',')?;
+            runner.chip.write_memory(address(addr)?, &unhex(data)?)?;
+            "OK".to_string()
+        }
+        "Z" | "z" => {
+            let mut fields = args.split(',');
+            let (Some("0" | "1"), Some(addr)) = (fields.next(), fields.next()) else {
+                // Watchpoints aren't supported
+                return Ok(String::new());
+            };
+            let addr = address(addr)?;
+            match command.starts_with('Z') {
+                true => debugger.set_breakpoint(addr, None),
+                false => _ = debugger.clear_breakpoint(addr),
+            }
+            "OK".to_string()
+        }
+        "H" => "OK".to_string(),
+        "q" if args.starts_with("Supported") => "PacketSize=1000;qXfer:features:read+".to_string(),
+        "q" if args == "Attached" => "1".to_string(),
+        "q" if args.starts_with("Xfer:features:read:target.xml:") => {
+            let (offset, len) = split(args.rsplit(':').next().unwrap_or(""), ',')?;
+            let xml = target_xml();
+            let start = number(offset)?.min(xml.len());
+            let end = (start + number(len)?).min(xml.len());
+            let more = if end < xml.len() { "m" } else { "l" };
+            format!("{more}{}", &xml[start..end])
+        }
+        // Anything else is unsupported, which an empty reply says
+        _ => String::new(),
+    })
+}
+
+struct Session {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+    debugger: Debugger,
+}
+
+impl Session {
+    /// Reads the next packet, acknowledging it. `None` when the client hangs
+    /// up.
+    fn read_packet(&mut self) -> Result<Option<Packet>> {
+        loop {
+            let mut byte = [0];
+            if std::io::Read::read(&mut self.reader, &mut byte)? == 0 {
+                return Ok(None);
+            }
+            match byte[0] {
+                0x03 => return Ok(Some(Packet::Interrupt)),
+                b'$' => {}
+                // Acknowledgements, and anything between packets
+                _ => continue,
+            }
+            let mut data = Vec::new();
+            self.reader.read_until(b'#', &mut data)?;
+            data.pop();
+            let mut checksum = [0; 2];
+            std::io::Read::read_exact(&mut self.reader, &mut checksum)?;
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|cs| u8::from_str_radix(cs, 16).ok());
+            if expected != Some(sum(&data)) {
+                self.stream.write_all(b"-")?;
+                continue;
+            }
+            self.stream.write_all(b"+")?;
+            return Ok(Some(Packet::Command(
+                String::from_utf8_lossy(&data).into_owned(),
+            )));
+        }
+    }
+
+    fn send(&mut self, data: &str) -> Result<()> {
+        write!(self.stream, "${data}#{:02x}", sum(data.as_bytes()))?;
+        Ok(())
+    }
+
+    /// Whether the client has sent an interrupt or hung up, without
+    /// blocking.
+    fn interrupted(&mut self) -> Result<bool> {
+        self.reader.get_ref().set_nonblocking(true)?;
+        let stop = match self.reader.fill_buf() {
+            Ok(buffer) => buffer.first().is_none_or(|byte| *byte == 0x03),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => false,
+            Err(e) => Err(e)?,
+        };
+        self.reader.get_ref().set_nonblocking(false)?;
+        if stop && !self.reader.buffer().is_empty() {
+            self.reader.consume(1);
+        }
+        Ok(stop)
+    }
+
+    /// Runs at 60 Hz until a breakpoint, a fault or an interrupt, returning
+    /// the stop reply.
+    fn run(&mut self, runner: &mut Runner) -> Result<String> {
+        let keypad = Keypad::default();
+        let frame_time = Duration::from_secs_f64(1.0 / 60.0);
+        // Step off a breakpoint at the current address first
+        if runner.step(&keypad).is_err() {
+            return Ok(SIGILL.to_string());
+        }
+        self.debugger.resume();
+        loop {
+            let frame_start = Instant::now();
+            if runner.frame(&keypad, &mut self.debugger).is_err() {
+                return Ok(SIGILL.to_string());
+            }
+            if self.debugger.paused() {
+                return Ok(SIGTRAP.to_string());
+            }
+            if self.interrupted()? {
+                self.debugger.pause();
+                return Ok(SIGINT.to_string());
+            }
+            std::thread::sleep(frame_time.saturating_sub(frame_start.elapsed()));
+        }
+    }
+}
+
+fn read_register(runner: &Runner, n: usize) -> String {
+    let chip = &runner.chip;
+    let value = match n {
+        0..16 => chip.registers()[n] as u16,
+        16 => chip.index(),
+        17 => chip.pc(),
+        18 => chip.call_stack().len() as u16,
+        19 => chip.timers().0 as u16,
+        20 => chip.timers().1 as u16,
+        _ => return String::new(),
+    };
+    hex(&value.to_le_bytes()[..REGISTERS[n].1])
+}
+
+fn write_register(runner: &mut Runner, n: usize, value: &str) -> Result<()> {
+    let bytes = unhex(value)?;
+    let value = u16::from_le_bytes([
+        bytes.first().copied().unwrap_or(0),
+        bytes.get(1).copied().unwrap_or(0),
+    ]);
+    let chip = &mut runner.chip;
+    let (delay, sound) = chip.timers();
+    match n {
+        0..16 => chip.set_register(n as u8, value as u8)?,
+        16 => chip.set_index_register(value),
+        17 => chip.set_pc(value % Memory::MEMORY_SIZE as u16),
+        18 => chip.set_stack_depth(value as usize),
+        19 => chip.set_timers(value as u8, sound),
+        20 => chip.set_timers(delay, value as u8),
+        _ => return Err(Error::Unknown(format!("no register {n}"))),
+    }
+    Ok(())
+}
+
+/// The register layout, so clients don't have to guess it.
+fn target_xml() -> String {
+    let registers = REGISTERS
+        .iter()
+        .map(|(name, size)| {
+            let kind = match *name {
+                "pc" => " type=\"code_ptr\"",
+                "i" => " type=\"data_ptr\"",
+                _ => "",
+            };
+            format!("<reg name=\"{name}\" bitsize=\"{}\"{kind}/>", size * 8)
+        })
+        .collect::<String>();
+    format!(
+        "<?xml version=\"1.0\"?><target version=\"1.0\">\
+         <architecture>chip8</architecture><feature name=\"org.chip8.core\">{registers}</feature></target>"
+    )
+}
+
+fn sum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, b| sum.wrapping_add(*b))
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(text: &str) -> Result<Vec<u8>> {
+    (0..text.len() / 2 * 2)
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&text[i..i + 2], 16)
+                .map_err(|_| Error::Unknown(format!("bad hex: {text}")))
+        })
+        .collect()
+}
+
+fn number(text: &str) -> Result<usize> {
+    usize::from_str_radix(text, 16).map_err(|_| Error::Unknown(format!("bad number: {text}")))
+}
+
+fn address(text: &str) -> Result<u16> {
+    u16::try_from(number(text)?).map_err(|_| Error::Unknown(format!("bad address: {text}")))
+}
+
+fn split(text: &str, separator: char) -> Result<(&str, &str)> {
+    text.split_once(separator)
+        .ok_or_else(|| Error::Unknown(format!("malformed packet: {text}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+
+    /// A scripted client: sends each packet and returns the reply.
+    struct Client {
+        reader: BufReader<TcpStream>,
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn request(&mut self, data: &str) -> String {
+            write!(self.stream, "${data}#{:02x}", sum(data.as_bytes())).unwrap();
+            self.reply()
+        }
+
+        fn reply(&mut self) -> String {
+            let mut ack = [0];
+            std::io::Read::read_exact(&mut self.reader, &mut ack).unwrap();
+            assert_eq!(ack[0], b'+');
+            let mut packet = Vec::new();
+            self.reader.read_until(b'#', &mut packet).unwrap();
+            let mut checksum = [0; 2];
+            std::io::Read::read_exact(&mut self.reader, &mut checksum).unwrap();
+            let text = String::from_utf8(packet[1..packet.len() - 1].to_vec()).unwrap();
+            assert_eq!(
+                u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(),
+                sum(text.as_bytes())
+            );
+            text
+        }
+    }
+
+    #[test]
+    fn test_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut chip = Chip8::default();
+            // 0x200: LD V0, 0x05; 0x202: ADD V0, 0x01; 0x204: JP 0x202
+            chip.load_rom(&[0x60, 0x05, 0x70, 0x01, 0x12, 0x02])
+                .unwrap();
+            let mut runner = Runner::new(chip, 10);
+            serve(&listener, &mut runner).unwrap();
+            runner.chip.registers()[1]
+        });
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut client = Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            stream,
+        };
+
+        assert!(
+            client
+                .request("qSupported:xmlRegisters=i386")
+                .contains("qXfer")
+        );
+        assert!(
+            client
+                .request("qXfer:features:read:target.xml:0,1000")
+                .starts_with("l<?xml")
+        );
+        assert_eq!(client.request("?"), "S05");
+        assert_eq!(client.request("p11"), "0002");
+        assert_eq!(client.request("s"), "S05");
+        assert_eq!(client.request("p0"), "05");
+        assert_eq!(client.request("m200,4"), "60057001");
+
+        assert_eq!(client.request("Z0,204,2"), "OK");
+        assert_eq!(client.request("c"), "S05");
+        assert_eq!(client.request("p11"), "0402");
+        assert_eq!(client.request("p0"), "06");
+        assert_eq!(client.request("z0,204,2"), "OK");
+
+        assert_eq!(client.request("P1=2a"), "OK");
+        assert_eq!(client.request("M300,2:beef"), "OK");
+        assert_eq!(client.request("m300,2"), "beef");
+        assert_eq!(client.request("m10300,2"), "E01");
+        assert_eq!(client.request("mfff,2"), "E01");
+        let registers = client.request("g");
+        assert_eq!(registers.len(), 2 * 23);
+        assert!(registers.starts_with("062a"));
+
+        // Interrupt a free run
+        write!(client.stream, "$c#{:02x}", sum(b"c")).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        client.stream.write_all(&[0x03]).unwrap();
+        assert_eq!(client.reply(), "S02");
+
+        assert_eq!(client.request("D"), "OK");
+        assert_eq!(server.join().unwrap(), 0x2A);
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::display::PixelSource;
+use crate::error::Result;
+use crate::palette::{Palette, Rgb};
+
+/// Frames per second of the recorded display, one per 60 Hz tick.
+const FRAME_RATE: u64 = 60;
+
+/// Shortest delay written, in centiseconds. Browsers and most viewers treat
+/// 0 or 1 as 10, which would play fast animations far too slowly.
+const MIN_DELAY: u64 = 2;
+
+/// Records 60 Hz display frames into an animated GIF. Runs of identical frames
+/// are merged into one image with a longer delay, and frames shown for less
+/// than `MIN_DELAY` are dropped in favour of the next one, so the result plays
+/// at up to 50 fps.
+#[derive(Debug)]
+pub struct GifRecorder<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+    scale: usize,
+    pending: Option<(Vec<u8>, u64)>,
+    /// Frames written out so far, used to keep delays in sync with 60 Hz.
+    frames_written: u64,
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(
+        mut out: W,
+        width: usize,
+        height: usize,
+        scale: usize,
+        palette: &Palette,
+    ) -> Result<Self> {
+        let scale = scale.max(1);
+        out.write_all(b"GIF89a")?;
+        out.write_all(&((width * scale) as u16).to_le_bytes())?;
+        out.write_all(&((height * scale) as u16).to_le_bytes())?;
+        // global colour table of 4 entries, 8 bits per primary
+        out.write_all(&[0b1111_0001, 0, 0])?;
+        for index in 0..4 {
+            let Rgb(r, g, b) = palette.color(index);
+            out.write_all(&[r, g, b])?;
+        }
+        // loop forever
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(Self {
+            out,
+            width,
+            height,
+            scale,
+            pending: None,
+            frames_written: 0,
+        })
+    }
+
+    pub fn frame<P: PixelSource>(&mut self, pixels: &P) -> Result<()> {
+        let (w, h) = (self.width * self.scale, self.height * self.scale);
+        let indices = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .map(|(x, y)| pixels.color_index(x / self.scale, y / self.scale) & 0x3)
+            .collect::<Vec<_>>();
+        match &mut self.pending {
+            Some((pending, frames)) if *pending == indices => *frames += 1,
+            Some((pending, frames)) if delay(self.frames_written, *frames) < MIN_DELAY => {
+                *pending = indices;
+                *frames += 1;
+            }
+            _ => {
+                self.flush_pending()?;
+                self.pending = Some((indices, 1));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.flush_pending()?;
+        self.out.write_all(b"\x3B")?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn flush_pending(&mut self) -> Result<()> {
+        let Some((indices, frames)) = self.pending.take() else {
+            return Ok(());
+        };
+        // Only the last frame can still be short
+        let delay = delay(self.frames_written, frames).max(MIN_DELAY);
+        self.frames_written += frames;
+
+        let (w, h) = (self.width * self.scale, self.height * self.scale);
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.out.write_all(&(delay as u16).to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&(w as u16).to_le_bytes())?;
+        self.out.write_all(&(h as u16).to_le_bytes())?;
+        self.out.write_all(&[0x00, LZW_MIN_CODE_SIZE])?;
+        for block in lzw_encode(&indices).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])?;
+        Ok(())
+    }
+}
+
+/// Delay in centiseconds for `frames` frames following `written` ones. GIF
+/// delays are whole centiseconds, so the rounding is spread over the frames.
+fn delay(written: u64, frames: u64) -> u64 {
+    let centis = |frames: u64| (frames * 100 + FRAME_RATE / 2) / FRAME_RATE;
+    centis(written + frames) - centis(written)
+}
+
+const LZW_MIN_CODE_SIZE: u8 = 2;
+
+/// GIF flavoured LZW: variable code width up to 12 bits, packed LSB first.
+fn lzw_encode(indices: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << LZW_MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut out = BitWriter::default();
+
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end + 1;
+    let mut width = LZW_MIN_CODE_SIZE as u32 + 1;
+    out.write(clear, width);
+    let mut prefix: Option<u16> = None;
+    for &index in indices {
+        let Some(current) = prefix else {
+            prefix = Some(index as u16);
+            continue;
+        };
+        if let Some(&code) = table.get(&(current, index)) {
+            prefix = Some(code);
+            continue;
+        }
+        out.write(current, width);
+        if next_code < 4096 {
+            table.insert((current, index), next_code);
+            next_code += 1;
+            if next_code > (1 << width) && width < 12 {
+                width += 1;
+            }
+        } else {
+            out.write(clear, width);
+            table.clear();
+            next_code = end + 1;
+            width = LZW_MIN_CODE_SIZE as u32 + 1;
+        }
+        prefix = Some(index as u16);
+    }
+    if let Some(current) = prefix {
+        out.write(current, width);
+    }
+    out.write(end, width);
+    out.finish()
+}
+
+#[derive(Debug, Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u32) {
+        self.bits |= (code as u32) << self.bit_count;
+        self.bit_count += width;
+        while self.bit_count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::DisplayBuffer;
+
+    /// Straightforward GIF LZW decoder to check the encoder against.
+    fn lzw_decode(data: &[u8]) -> Vec<u8> {
+        let clear = 1u16 << LZW_MIN_CODE_SIZE;
+        let end = clear + 1;
+        let mut out = Vec::new();
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            *table = (0..clear).map(|i| vec![i as u8]).collect();
+            table.push(Vec::new());
+            table.push(Vec::new());
+        };
+        reset(&mut table);
+        let mut width = LZW_MIN_CODE_SIZE as u32 + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let (mut bits, mut bit_count, mut pos) = (0u32, 0u32, 0);
+        loop {
+            while bit_count < width {
+                bits |= (data[pos] as u32) << bit_count;
+                pos += 1;
+                bit_count += 8;
+            }
+            let code = (bits & ((1 << width) - 1)) as u16;
+            bits >>= width;
+            bit_count -= width;
+            if code == clear {
+                reset(&mut table);
+                width = LZW_MIN_CODE_SIZE as u32 + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (table.get(code as usize), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(prev)) => [prev.clone(), vec![prev[0]]].concat(),
+                (None, None) => panic!("bad code"),
+            };
+            out.extend(&entry);
+            if let Some(prev) = previous {
+                table.push([prev, vec![entry[0]]].concat());
+                if table.len() == (1 << width) && width < 12 {
+                    width += 1;
+                }
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw_roundtrip() {
+        let data = (0..20_000u32)
+            .map(|i| ((i * 7 + i / 13) % 4) as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(lzw_decode(&lzw_encode(&data)), data);
+        let flat = vec![0u8; 5000];
+        assert_eq!(lzw_decode(&lzw_encode(&flat)), flat);
+    }
+
+    #[test]
+    fn test_lzw_known_stream() {
+        // The 10x10 sample image from "What's In A GIF" (Matthew Flickinger)
+        let rows = [
+            "1111122222",
+            "1111122222",
+            "1111122222",
+            "1110000222",
+            "1110000222",
+            "2220000111",
+            "2220000111",
+            "2222211111",
+            "2222211111",
+            "2222211111",
+        ];
+        let indices = rows.concat().bytes().map(|b| b - b'0').collect::<Vec<_>>();
+        assert_eq!(
+            lzw_encode(&indices),
+            [
+                0x8C, 0x2D, 0x99, 0x87, 0x2A, 0x1C, 0xDC, 0x33, 0xA0, 0x02, 0x75, 0xEC, 0x95, 0xFA,
+                0xA8, 0xDE, 0x60, 0x8C, 0x04, 0x91, 0x4C, 0x01
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frame_timing() {
+        let mut display = DisplayBuffer::default();
+        let mut recorder = GifRecorder::new(
+            Vec::new(),
+            DisplayBuffer::WIDTH,
+            DisplayBuffer::HEIGHT,
+            1,
+            &Palette::CLASSIC,
+        )
+        .unwrap();
+        for i in 0..60 {
+            display.set(0usize, 0, (i % 2 == 0) as u8).unwrap();
+            recorder.frame(&display).unwrap();
+        }
+        for _ in 0..60 {
+            recorder.frame(&display).unwrap();
+        }
+        let out = recorder.finish().unwrap();
+        assert_eq!(&out[..6], b"GIF89a");
+        assert_eq!(out.last(), Some(&0x3B));
+        let delays = out
+            .windows(6)
+            .filter(|w| w[..3] == [0x21, 0xF9, 0x04])
+            .map(|w| u16::from_le_bytes([w[4], w[5]]))
+            .collect::<Vec<_>>();
+        // Some alternating frames are dropped to keep delays long enough
+        assert!(delays.len() < 60);
+        assert!(delays.iter().all(|&delay| delay >= MIN_DELAY as u16));
+        assert_eq!(delays.iter().sum::<u16>(), 200);
+    }
+}
@@ -0,0 +1,302 @@
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled};
+
+use crate::display::PixelSource;
+use crate::error::{Error, Result};
+use crate::palette::{Palette, Rgb};
+
+/// Terminal bitmap protocols, drawing the frame as an image instead of cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Sixel,
+    Kitty,
+}
+
+impl GraphicsProtocol {
+    /// Asks the terminal what it supports, falling back to what it
+    /// advertises in its environment when it doesn't answer.
+    pub fn detect() -> Option<Self> {
+        match query_terminal() {
+            Some(reply) => Self::from_reply(&reply),
+            None => Self::from_env(),
+        }
+    }
+
+    /// Support as shown by the replies to `QUERY`: an OK for the kitty query,
+    /// or attribute 4 in the primary device attributes for sixel.
+    fn from_reply(reply: &[u8]) -> Option<Self> {
+        let reply = String::from_utf8_lossy(reply);
+        if reply.contains("\x1b_Gi=31;OK") {
+            return Some(GraphicsProtocol::Kitty);
+        }
+        let (_, attributes) = reply.split_once("\x1b[?")?;
+        let (attributes, _) = attributes.split_once('c')?;
+        attributes
+            .split(';')
+            .any(|attribute| attribute == "4")
+            .then_some(GraphicsProtocol::Sixel)
+    }
+
+    fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        let program = var("TERM_PROGRAM");
+        if !var("KITTY_WINDOW_ID").is_empty() || term.contains("kitty") || program == "ghostty" {
+            Some(GraphicsProtocol::Kitty)
+        } else if term.contains("sixel")
+            || term.starts_with("foot")
+            || term.starts_with("mlterm")
+            || program == "WezTerm"
+        {
+            Some(GraphicsProtocol::Sixel)
+        } else {
+            None
+        }
+    }
+
+    /// Escape sequence drawing the pixels at the cursor, each pixel scaled up
+    /// to a `scale` x `scale` square.
+    pub fn encode<P: PixelSource + ?Sized>(
+        &self,
+        pixels: &P,
+        scale: usize,
+        palette: &Palette,
+    ) -> String {
+        match self {
+            GraphicsProtocol::Sixel => sixel(pixels, scale.max(1), palette),
+            GraphicsProtocol::Kitty => kitty(pixels, scale.max(1), palette),
+        }
+    }
+}
+
+/// A kitty graphics query for a 1x1 image, then a primary device attributes
+/// request. Every terminal answers the latter, so its reply marks the end of
+/// whatever came back.
+const QUERY: &[u8] = b"\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[c";
+
+/// How long to wait for the terminal to answer `QUERY`.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Sends `QUERY` with the terminal in raw mode and returns everything read
+/// up to the end of the device attributes. `None` when stdin isn't a
+/// terminal or it doesn't answer in time.
+fn query_terminal() -> Option<Vec<u8>> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let was_raw = is_raw_mode_enabled().ok()?;
+    enable_raw_mode().ok()?;
+    let mut stdout = std::io::stdout();
+    let sent = stdout.write_all(QUERY).and_then(|()| stdout.flush());
+    let reply = sent
+        .ok()
+        .and_then(|()| read_reply(Instant::now() + QUERY_TIMEOUT));
+    if !was_raw {
+        _ = disable_raw_mode();
+    }
+    reply
+}
+
+/// Reads stdin a byte at a time up to the end of the device attributes, so
+/// nothing typed after the reply is taken. `None` at `deadline`.
+#[cfg(unix)]
+fn read_reply(deadline: Instant) -> Option<Vec<u8>> {
+    use std::os::fd::AsRawFd;
+
+    let fd = std::io::stdin().as_raw_fd();
+    let mut reply = Vec::new();
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let mut ready = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `ready` is one valid pollfd for the duration of the call
+        if unsafe { libc::poll(&mut ready, 1, timeout.as_millis() as libc::c_int) } <= 0 {
+            return None;
+        }
+        let mut byte = 0u8;
+        // SAFETY: reads at most one byte into `byte`. Going around std's
+        // buffered stdin leaves later input for crossterm
+        if unsafe { libc::read(fd, (&raw mut byte).cast(), 1) } != 1 {
+            return None;
+        }
+        reply.push(byte);
+        if byte == b'c' && reply.windows(3).any(|w| w == b"\x1b[?") {
+            return Some(reply);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn read_reply(_deadline: Instant) -> Option<Vec<u8>> {
+    None
+}
+
+fn sixel<P: PixelSource + ?Sized>(pixels: &P, scale: usize, palette: &Palette) -> String {
+    let (width, height) = pixels.size();
+    let (w, h) = (width * scale, height * scale);
+    let mut out = format!("\x1bPq\"1;1;{w};{h}");
+    for index in 0..4 {
+        let Rgb(r, g, b) = palette.color(index);
+        let percent = |c: u8| c as u32 * 100 / 255;
+        out.push_str(&format!(
+            "#{index};2;{};{};{}",
+            percent(r),
+            percent(g),
+            percent(b)
+        ));
+    }
+    for band in (0..h).step_by(6) {
+        for index in 0..4u8 {
+            let column = |x: usize| {
+                (0..6)
+                    .filter(|dy| band + dy < h)
+                    .filter(|dy| pixels.color_index(x / scale, (band + dy) / scale) == index)
+                    .fold(0u8, |bits, dy| bits | (1 << dy))
+            };
+            let columns = (0..w).map(column).collect::<Vec<_>>();
+            if columns.iter().all(|bits| *bits == 0) {
+                continue;
+            }
+            out.push_str(&format!("#{index}"));
+            let mut x = 0;
+            while x < columns.len() {
+                let run = columns[x..]
+                    .iter()
+                    .take_while(|c| **c == columns[x])
+                    .count();
+                let ch = (0x3F + columns[x]) as char;
+                if run > 3 {
+                    out.push_str(&format!("!{run}{ch}"));
+                } else {
+                    (0..run).for_each(|_| out.push(ch));
+                }
+                x += run;
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+fn kitty<P: PixelSource + ?Sized>(pixels: &P, scale: usize, palette: &Palette) -> String {
+    const CHUNK: usize = 4096;
+    let (width, height) = pixels.size();
+    let (w, h) = (width * scale, height * scale);
+    let rgb = (0..h)
+        .flat_map(|y| (0..w).map(move |x| (x, y)))
+        .flat_map(|(x, y)| {
+            let Rgb(r, g, b) = palette.color(pixels.color_index(x / scale, y / scale));
+            [r, g, b]
+        })
+        .collect::<Vec<_>>();
+    let data = base64(&rgb);
+    let chunks = data.as_bytes().chunks(CHUNK).collect::<Vec<_>>();
+    let mut out = String::with_capacity(data.len() + chunks.len() * 16);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        // Reusing the image and placement ids replaces the previous frame
+        let control = if i == 0 {
+            format!("a=T,f=24,s={w},v={h},i=1,p=1,q=2,C=1,m={more}")
+        } else {
+            format!("m={more}")
+        };
+        out.push_str(&format!(
+            "\x1b_G{control};{}\x1b\\",
+            String::from_utf8_lossy(chunk)
+        ));
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+impl std::str::FromStr for GraphicsProtocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sixel" => Ok(GraphicsProtocol::Sixel),
+            "kitty" => Ok(GraphicsProtocol::Kitty),
+            _ => Err(Error::Unknown(format!("unknown graphics protocol: {s}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::DisplayBuffer;
+
+    #[test]
+    fn test_detect_from_reply() {
+        let detect = |reply: &str| GraphicsProtocol::from_reply(reply.as_bytes());
+        assert_eq!(
+            detect("\x1b_Gi=31;OK\x1b\\\x1b[?62;22c"),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(detect("\x1b[?65;4;6;18;22c"), Some(GraphicsProtocol::Sixel));
+        assert_eq!(detect("\x1b[?62;c"), None);
+        assert_eq!(detect("\x1b[?64;1;2;6;9;15;16;17;18;21;22;28c"), None);
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_sixel() {
+        let mut display = DisplayBuffer::default();
+        display.set(0usize, 0, 1).unwrap();
+        let out = GraphicsProtocol::Sixel.encode(&display, 1, &Palette::CLASSIC);
+        assert!(out.starts_with("\x1bPq\"1;1;64;32#0;2;0;0;0#1;2;100;100;100"));
+        assert!(out.ends_with("\x1b\\"));
+        // first band: pixel (0, 0) in colour 1, everything else colour 0
+        assert!(out.contains("#0}!63~$#1@!63?$-"));
+        assert_eq!(out.matches('-').count(), 6);
+    }
+
+    #[test]
+    fn test_kitty_chunks() {
+        let display = DisplayBuffer::default();
+        let out = GraphicsProtocol::Kitty.encode(&display, 2, &Palette::CLASSIC);
+        let chunks = out
+            .split("\x1b\\")
+            .filter(|c| !c.is_empty())
+            .collect::<Vec<_>>();
+        // 128 * 64 * 3 bytes of RGB is 32768 base64 characters
+        assert_eq!(chunks.len(), 8);
+        assert!(chunks[0].starts_with("\x1b_Ga=T,f=24,s=128,v=64,"));
+        assert!(chunks[0].contains(",m=1;"));
+        assert!(chunks[7].starts_with("\x1b_Gm=0;"));
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+
+use crate::{
+    chip8::{Chip8, CpuState},
+    display::DisplayBuffer,
+    error::Result,
+};
+
+/// Instructions between snapshots.
+const SNAPSHOT_INTERVAL: usize = 1024;
+/// Chunks that keep their per-instruction undo logs. Older chunks keep only
+/// their snapshot, so stepping back past them goes a chunk at a time.
+const FINE_CHUNKS: usize = 16;
+/// Snapshots kept in total, about five minutes at the default speed.
+const MAX_CHUNKS: usize = 256;
+
+/// What one instruction changed, enough to put it back.
+#[derive(Debug)]
+struct Undo {
+    cpu: CpuState,
+    /// Written addresses with their previous bytes, in write order.
+    memory: Vec<(u16, u8)>,
+    /// Pixels the instruction changed, with their previous state.
+    pixels: Vec<(usize, usize, u8)>,
+}
+
+/// A snapshot of the whole machine and the instructions run since it.
+#[derive(Debug)]
+struct Chunk {
+    snapshot: Chip8,
+    /// `None` once trimmed to save memory.
+    undo: Option<Vec<Undo>>,
+    instructions: usize,
+}
+
+/// Execution history for stepping backwards: per-instruction undo logs for
+/// recent instructions, on top of periodic snapshots of the whole machine.
+#[derive(Debug, Default)]
+pub struct History {
+    chunks: VecDeque<Chunk>,
+}
+
+impl History {
+    /// Runs one instruction through `execute`, logging how to undo it. The
+    /// chip's access log must be on for memory writes to be undone.
+    pub fn record(
+        &mut self,
+        chip: &mut Chip8,
+        execute: impl FnOnce(&mut Chip8) -> Result<()>,
+    ) -> Result<()> {
+        if self
+            .chunks
+            .back()
+            .is_none_or(|chunk| chunk.instructions >= SNAPSHOT_INTERVAL)
+        {
+            self.start_chunk(chip);
+        }
+        let cpu = chip.cpu_state();
+        let mark = chip.access_count();
+        // Only 00E0 and DXYN instructions draw
+        let pixels_before = chip
+            .next_opcode()
+            .is_ok_and(|opcode| matches!(opcode.code(), 0x0 | 0xD))
+            .then_some(chip.display_buffer.pixels);
+
+        let result = execute(chip);
+
+        let memory = chip
+            .accesses_since(mark)
+            .iter()
+            .filter(|access| access.write)
+            .map(|access| (access.addr, access.previous))
+            .collect();
+        let pixels = pixels_before.map_or_else(Vec::new, |before| {
+            (0..DisplayBuffer::HEIGHT)
+                .flat_map(|y| (0..DisplayBuffer::WIDTH).map(move |x| (x, y)))
+                .filter(|&(x, y)| chip.display_buffer.pixels[y][x] != before[y][x])
+                .map(|(x, y)| (x, y, before[y][x]))
+                .collect()
+        });
+        if let Some(chunk) = self.chunks.back_mut() {
+            chunk.instructions += 1;
+            if let Some(undo) = &mut chunk.undo {
+                undo.push(Undo {
+                    cpu,
+                    memory,
+                    pixels,
+                });
+            }
+        }
+        result
+    }
+
+    fn start_chunk(&mut self, chip: &Chip8) {
+        self.chunks.push_back(Chunk {
+            snapshot: chip.clone(),
+            undo: Some(Vec::new()),
+            instructions: 0,
+        });
+        if self.chunks.len() > MAX_CHUNKS {
+            self.chunks.pop_front();
+        }
+        if let Some(old) = self
+            .chunks
+            .len()
+            .checked_sub(FINE_CHUNKS + 1)
+            .and_then(|i| self.chunks.get_mut(i))
+        {
+            old.undo = None;
+        }
+    }
+
+    /// Puts the machine back to before its last instruction, or before the
+    /// last whole chunk once past the undo logs. Returns how many
+    /// instructions were undone, `None` when the history is used up.
+    pub fn step_back(&mut self, chip: &mut Chip8) -> Option<usize> {
+        loop {
+            let chunk = self.chunks.back_mut()?;
+            match &mut chunk.undo {
+                Some(undo) => match undo.pop() {
+                    Some(step) => {
+                        chunk.instructions -= 1;
+                        for (addr, previous) in step.memory.iter().rev() {
+                            let _ = chip.write_memory(*addr, &[*previous]);
+                        }
+                        for (x, y, previous) in step.pixels {
+                            let _ = chip.display_buffer.set(x, y, previous);
+                        }
+                        chip.restore_cpu_state(step.cpu);
+                        return Some(1);
+                    }
+                    // At the snapshot already, so carry on into the chunk
+                    // before
+                    None => _ = self.chunks.pop_back(),
+                },
+                None => {
+                    let chunk = self.chunks.pop_back()?;
+                    *chip = chunk.snapshot;
+                    chip.take_accesses();
+                    chip.display_buffer.mark_dirty();
+                    if chunk.instructions > 0 {
+                        return Some(chunk.instructions);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Keypad;
+
+    #[test]
+    fn test_step_back() {
+        let mut chip = Chip8::default();
+        // 0x200: LD I, 0x300; 0x202: LD V0, 0x05; 0x204: LD [I], V0;
+        // 0x206: DRW V0, V0, 1; 0x208: CALL 0x208
+        chip.load_rom(&[0xA3, 0x00, 0x60, 0x05, 0xF0, 0x55, 0xD0, 0x01, 0x22, 0x08])
+            .unwrap();
+        chip.set_access_log(true);
+        let mut history = History::default();
+        let keypad = Keypad::default();
+        for _ in 0..5 {
+            history
+                .record(&mut chip, |chip| chip.cycle(&keypad))
+                .unwrap();
+        }
+        assert_eq!(chip.pc(), 0x208);
+        assert_eq!(chip.call_stack(), [0x208]);
+        assert_eq!(chip.display_buffer.pixels[5][10], 1);
+
+        assert_eq!(history.step_back(&mut chip), Some(1));
+        assert_eq!(chip.pc(), 0x208);
+        assert!(chip.call_stack().is_empty());
+        assert_eq!(history.step_back(&mut chip), Some(1));
+        assert_eq!(chip.display_buffer.pixels[5][10], 0);
+        assert_eq!(history.step_back(&mut chip), Some(1));
+        assert_eq!(chip.read_memory(0x300, 1).unwrap(), [0]);
+        assert_eq!(history.step_back(&mut chip), Some(1));
+        assert_eq!(history.step_back(&mut chip), Some(1));
+        assert_eq!((chip.pc(), chip.index()), (0x200, 0));
+        assert_eq!(history.step_back(&mut chip), None);
+    }
+
+    #[test]
+    fn test_old_chunks_step_back_whole() {
+        let mut chip = Chip8::default();
+        // 0x200: ADD V0, 0x01; 0x202: JP 0x200
+        chip.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut history = History::default();
+        let keypad = Keypad::default();
+        let total = SNAPSHOT_INTERVAL * (FINE_CHUNKS + 2);
+        for _ in 0..total {
+            history
+                .record(&mut chip, |chip| chip.cycle(&keypad))
+                .unwrap();
+        }
+        let mut undone = 0;
+        while undone < SNAPSHOT_INTERVAL * FINE_CHUNKS {
+            undone += history.step_back(&mut chip).unwrap();
+        }
+        assert_eq!(history.step_back(&mut chip), Some(SNAPSHOT_INTERVAL));
+        assert_eq!(chip.registers()[0], (SNAPSHOT_INTERVAL / 2) as u8);
+        assert_eq!(chip.pc(), 0x200);
+    }
+}
@@ -0,0 +1,269 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::display::PixelSource;
+use crate::error::{Error, Result};
+use crate::palette::{Palette, Rgb};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    /// Binary RGB portable pixmap.
+    Ppm,
+    /// Binary portable bitmap, lit pixels are 1 (black) whatever the palette.
+    Pbm,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("png") => Ok(ImageFormat::Png),
+            Some("ppm") => Ok(ImageFormat::Ppm),
+            Some("pbm") => Ok(ImageFormat::Pbm),
+            _ => Err(Error::Unknown(format!(
+                "unsupported image format: {}",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Writes the pixels to `path`, each pixel scaled up to a `scale` x `scale`
+/// square. The format is taken from the file extension.
+pub fn save_image<P: PixelSource>(
+    path: &Path,
+    pixels: &P,
+    scale: usize,
+    palette: &Palette,
+) -> Result<()> {
+    let format = ImageFormat::from_path(path)?;
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_image(&mut out, pixels, format, scale, palette)
+}
+
+pub fn write_image<W: Write, P: PixelSource>(
+    out: &mut W,
+    pixels: &P,
+    format: ImageFormat,
+    scale: usize,
+    palette: &Palette,
+) -> Result<()> {
+    let scale = scale.max(1);
+    let (width, height) = pixels.size();
+    let (w, h) = (width * scale, height * scale);
+    let rgb_rows = || {
+        (0..h).map(move |y| {
+            (0..w)
+                .flat_map(|x| {
+                    let Rgb(r, g, b) = palette.color(pixels.color_index(x / scale, y / scale));
+                    [r, g, b]
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+    match format {
+        ImageFormat::Ppm => {
+            write!(out, "P6\n{w} {h}\n255\n")?;
+            for row in rgb_rows() {
+                out.write_all(&row)?;
+            }
+        }
+        ImageFormat::Pbm => {
+            write!(out, "P4\n{w} {h}\n")?;
+            for y in 0..h {
+                let mut row = vec![0u8; w.div_ceil(8)];
+                for x in (0..w).filter(|x| pixels.color_index(x / scale, y / scale) != 0) {
+                    row[x / 8] |= 0x80 >> (x % 8);
+                }
+                out.write_all(&row)?;
+            }
+        }
+        ImageFormat::Png => {
+            let mut raw = Vec::with_capacity(h * (w * 3 + 1));
+            for row in rgb_rows() {
+                raw.push(0); // no filter
+                raw.extend(row);
+            }
+            let mut ihdr = Vec::new();
+            ihdr.extend((w as u32).to_be_bytes());
+            ihdr.extend((h as u32).to_be_bytes());
+            ihdr.extend([8, 2, 0, 0, 0]); // 8-bit RGB, no interlace
+            out.write_all(b"\x89PNG\r\n\x1a\n")?;
+            write_png_chunk(out, b"IHDR", &ihdr)?;
+            write_png_chunk(out, b"IDAT", &zlib_stored(&raw))?;
+            write_png_chunk(out, b"IEND", &[])?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads a binary (`P4`) portable bitmap into rows of lit pixels.
+pub fn read_pbm(data: &[u8]) -> Result<Vec<Vec<bool>>> {
+    let bad = |what: &str| Error::Unknown(format!("bad PBM: {what}"));
+    let mut pos = 0;
+    let mut fields = Vec::new();
+    while fields.len() < 3 {
+        match data.get(pos) {
+            Some(b'#') => {
+                while data.get(pos).is_some_and(|b| *b != b'\n') {
+                    pos += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => pos += 1,
+            Some(_) => {
+                let start = pos;
+                while data.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+                    pos += 1;
+                }
+                fields.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+            }
+            None => return Err(bad("truncated header")),
+        }
+    }
+    if fields[0] != "P4" {
+        return Err(bad("not a binary bitmap"));
+    }
+    let width = fields[1].parse::<usize>().map_err(|_| bad("width"))?;
+    let height = fields[2].parse::<usize>().map_err(|_| bad("height"))?;
+    // A single whitespace byte separates the header from the raster
+    let raster = &data[pos + 1..];
+    let stride = width.div_ceil(8);
+    if raster.len() < stride * height {
+        return Err(bad("truncated raster"));
+    }
+    Ok(raster
+        .chunks(stride)
+        .take(height)
+        .map(|row| {
+            (0..width)
+                .map(|x| row[x / 8] & (0x80 >> (x % 8)) != 0)
+                .collect()
+        })
+        .collect())
+}
+
+fn write_png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let mut crc_input = kind.to_vec();
+    crc_input.extend_from_slice(data);
+    out.write_all(&crc32(&crc_input).to_be_bytes())?;
+    Ok(())
+}
+
+/// Wraps `data` in a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut chunks = data.chunks(u16::MAX as usize).peekable();
+    if chunks.peek().is_none() {
+        out.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(last as u8);
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::DisplayBuffer;
+
+    fn test_display() -> DisplayBuffer {
+        let mut display = DisplayBuffer::default();
+        display.set(0usize, 0, 1).unwrap();
+        display.set(63usize, 31, 1).unwrap();
+        display
+    }
+
+    #[test]
+    fn test_pbm() {
+        let mut out = Vec::new();
+        let display = test_display();
+        write_image(&mut out, &display, ImageFormat::Pbm, 2, &Palette::CLASSIC).unwrap();
+        let header = b"P4\n128 64\n";
+        assert_eq!(&out[..header.len()], header);
+        let data = &out[header.len()..];
+        assert_eq!(data.len(), 16 * 64);
+        assert_eq!(data[0], 0xC0);
+        assert_eq!(data[16], 0xC0);
+        assert_eq!(data[32], 0x00);
+        assert_eq!(data[data.len() - 1], 0x03);
+    }
+
+    #[test]
+    fn test_read_pbm() {
+        let mut out = Vec::new();
+        write_image(
+            &mut out,
+            &test_display(),
+            ImageFormat::Pbm,
+            1,
+            &Palette::CLASSIC,
+        )
+        .unwrap();
+        let rows = read_pbm(&out).unwrap();
+        assert_eq!((rows.len(), rows[0].len()), (32, 64));
+        assert!(rows[0][0] && rows[31][63] && !rows[0][1]);
+
+        let rows = read_pbm(b"P4 # comment\n3 1\n\xA0").unwrap();
+        assert_eq!(rows, [[true, false, true]]);
+    }
+
+    #[test]
+    fn test_ppm() {
+        let mut out = Vec::new();
+        let display = test_display();
+        write_image(&mut out, &display, ImageFormat::Ppm, 1, &Palette::AMBER).unwrap();
+        let header = b"P6\n64 32\n255\n";
+        assert_eq!(&out[..header.len()], header);
+        assert_eq!(
+            &out[header.len()..header.len() + 6],
+            [0xFF, 0xB0, 0x00, 0x1A, 0x10, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_png() {
+        let mut out = Vec::new();
+        let display = test_display();
+        write_image(&mut out, &display, ImageFormat::Png, 1, &Palette::CLASSIC).unwrap();
+        assert_eq!(&out[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&out[12..16], b"IHDR");
+        assert_eq!(&out[out.len() - 8..out.len() - 4], b"IEND");
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+}
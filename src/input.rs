@@ -1,33 +1,82 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 use std::time::Duration;
 
+/// Emulator controls handled by the frontend rather than passed to the ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    Quit,
+    Screenshot,
+    ToggleRecording,
+    Pause,
+}
+
+/// Keyboard keys for CHIP-8 keys `0` to `F`, in key order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keymap([char; 16]);
+
+impl Default for Keymap {
+    /// The usual `1234`/`qwer`/`asdf`/`zxcv` block mapped onto the COSMAC VIP
+    /// hex keypad layout.
+    fn default() -> Self {
+        "x123qweasdzc4rfv".parse().expect("default keymap is valid")
+    }
+}
+
+impl std::str::FromStr for Keymap {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let keys = s.chars().collect::<Vec<_>>();
+        let keys: [char; 16] = keys
+            .try_into()
+            .map_err(|_| Error::Unknown(format!("keymap needs 16 keys: {s}")))?;
+        if let Some(key) = keys
+            .iter()
+            .find(|k| keys.iter().filter(|o| o == k).count() > 1)
+        {
+            return Err(Error::Unknown(format!("key {key:?} mapped twice in {s}")));
+        }
+        Ok(Self(keys))
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Keypad {
     pressed: [bool; 16],
+    keymap: Keymap,
 }
 
 impl Keypad {
-    pub fn poll(&mut self) -> Result<bool> {
-        if event::poll(Duration::from_millis(1))?
+    pub fn with_keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    pub fn poll(&mut self) -> Result<Option<Hotkey>> {
+        if event::poll(Duration::ZERO)?
             && let Event::Key(KeyEvent {
                 code, modifiers, ..
             }) = event::read()?
         {
             match (code, modifiers) {
                 (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-                    return Ok(true);
+                    return Ok(Some(Hotkey::Quit));
                 }
+                (KeyCode::F(12), _) => return Ok(Some(Hotkey::Screenshot)),
+                (KeyCode::F(9), _) => return Ok(Some(Hotkey::ToggleRecording)),
+                (KeyCode::F(5), _) => return Ok(Some(Hotkey::Pause)),
                 (code, _) => {
-                    if let Some(key) = Keypad::get_key_value(code) {
+                    if let Some(key) = self.get_key_value(code) {
                         self.pressed[key as usize] = true;
                     }
                 }
             }
         }
-        Ok(false)
+        Ok(None)
     }
 
+    #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.pressed = [false; 16];
     }
@@ -48,25 +97,14 @@ impl Keypad {
             .any(|(i, x)| *x && i == key as usize)
     }
 
-    fn get_key_value(key: KeyCode) -> Option<u8> {
+    fn get_key_value(&self, key: KeyCode) -> Option<u8> {
         match key {
-            // TODO use scancodes?
-            KeyCode::Char('1') => Some(0x1),
-            KeyCode::Char('2') => Some(0x2),
-            KeyCode::Char('3') => Some(0x3),
-            KeyCode::Char('4') => Some(0xC),
-            KeyCode::Char('q') => Some(0x4),
-            KeyCode::Char('w') => Some(0x5),
-            KeyCode::Char('e') => Some(0x6),
-            KeyCode::Char('r') => Some(0xD),
-            KeyCode::Char('a') => Some(0x7),
-            KeyCode::Char('s') => Some(0x8),
-            KeyCode::Char('d') => Some(0x9),
-            KeyCode::Char('f') => Some(0xE),
-            KeyCode::Char('z') => Some(0xA),
-            KeyCode::Char('x') => Some(0x0),
-            KeyCode::Char('c') => Some(0xB),
-            KeyCode::Char('v') => Some(0xF),
+            KeyCode::Char(c) => self
+                .keymap
+                .0
+                .iter()
+                .position(|k| k.eq_ignore_ascii_case(&c))
+                .map(|i| i as u8),
             _ => None,
         }
     }
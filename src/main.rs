@@ -1,77 +1,530 @@
+mod analysis;
+mod asm;
+mod audio;
+mod cast;
+mod cheats;
 mod chip8;
+mod cli;
+mod condition;
+mod config;
+mod debugger;
+mod disasm;
 mod display;
 mod error;
+mod flicker;
 mod font;
+mod gdb;
+mod gif;
+mod graphics;
+mod history;
+mod image;
 mod input;
 mod memory;
+mod memview;
+mod palette;
+mod profiler;
 mod program_counter;
+mod quirks;
 mod register;
+mod renderer;
+mod rng;
+mod romdb;
+mod runner;
+mod sha1;
 mod stack;
+mod terminal;
 mod timer;
+mod toml;
 mod utils;
 
 use std::{
     io::{self, Write},
+    path::Path,
     time::{Duration, Instant},
 };
 
 use crossterm::{
-    cursor::{self, Hide, Show},
-    execute, queue, style,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    cursor::{Hide, Show},
+    execute,
+    terminal::{
+        Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode,
+        enable_raw_mode,
+    },
 };
 
-use crate::{display::DisplayBuffer, input::Keypad, utils::debug_out};
+use crate::{
+    analysis::Analysis,
+    audio::{AudioSink, BellSink, NullSink, WavSink},
+    cast::CastWriter,
+    cheats::Cheats,
+    cli::{Command, RunArgs, RunOptions},
+    config::Config,
+    debugger::{Debugger, DebuggerAction},
+    display::DisplayBuffer,
+    flicker::FlickerMode,
+    gif::GifRecorder,
+    graphics::GraphicsProtocol,
+    input::{Hotkey, Keypad},
+    memory::Memory,
+    palette::Palette,
+    renderer::Renderer,
+    rng::Rng,
+    runner::{RunOutcome, Runner},
+    terminal::{TerminalFrontend, draw_panel},
+    utils::debug_out,
+};
 
 const FRAME_TIMEOUT: f32 = 1.0 / 60.0;
 
+/// Frames run by `--headless` and `test` when `--frames` isn't given.
+const DEFAULT_HEADLESS_FRAMES: u64 = 600;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let rom_path = std::env::args().nth(1).ok_or("usage: chip8 <rom_path>")?;
-    let rom = std::fs::read(&rom_path).map_err(|e| format!("failed to read rom: {e}"))?;
-    let mut chip = chip8::Chip8::default();
-    chip.load_rom(&rom)?;
+    match cli::parse(std::env::args().skip(1))? {
+        Command::Help(text) => println!("{text}"),
+        Command::Run(args) => {
+            let (options, rom) = resolve(&args)?;
+            if let Some(port) = options.gdb {
+                let mut runner = build_runner(&options, &rom)?;
+                let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+                println!("waiting for gdb on {}", listener.local_addr()?);
+                gdb::serve(&listener, &mut runner)?;
+                runner.finish()?;
+                write_profile(&options, &runner, &rom)?;
+            } else if options.headless {
+                let runner = run_headless(&options, &rom)?;
+                print!("{}", frame_text(&runner.chip.display_buffer));
+            } else {
+                run(options, &rom)?;
+            }
+        }
+        Command::Test {
+            args,
+            expect,
+            update,
+        } => {
+            let (options, rom) = resolve(&args)?;
+            test(&options, &rom, expect.as_deref(), update)?
+        }
+        Command::Disasm {
+            rom,
+            coverage,
+            cfg,
+            calls,
+        } => {
+            let rom = std::fs::read(&rom).map_err(|e| format!("failed to read rom: {e}"))?;
+            let program = romdb::program(&rom);
+            let executed = match coverage {
+                Some(path) => analysis::parse_trace(
+                    &std::fs::read_to_string(&path)
+                        .map_err(|e| format!("failed to read {}: {e}", path.display()))?,
+                ),
+                None => Vec::new(),
+            };
+            let analysis = Analysis::with_coverage(program, &executed);
+            for line in disasm::disassemble_rom(program, &analysis) {
+                println!("{line}");
+            }
+            if let Some(path) = cfg {
+                std::fs::write(path, analysis.cfg_dot(program))?;
+            }
+            if let Some(path) = calls {
+                std::fs::write(path, analysis.call_graph_dot())?;
+            }
+        }
+        Command::Asm { source, output } => {
+            let source = std::fs::read_to_string(&source)
+                .map_err(|e| format!("failed to read {}: {e}", source.display()))?;
+            std::fs::write(&output, asm::assemble(&source)?)?;
+        }
+        Command::Info(path) => info(&path)?,
+    }
+    Ok(())
+}
+
+/// Reads the ROM and merges the config file with the command line flags.
+fn resolve(args: &RunArgs) -> Result<(RunOptions, Vec<u8>), Box<dyn std::error::Error>> {
+    let rom = std::fs::read(&args.rom).map_err(|e| format!("failed to read rom: {e}"))?;
+    let config = Config::load(args.config.as_deref())?;
+    // Stays on screen after the alternate screen closes, next to any
+    // unknown opcode error
+    let unsupported = Analysis::new(romdb::program(&rom))
+        .unsupported()
+        .map(|op| op.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if !unsupported.is_empty() {
+        eprintln!(
+            "warning: {} uses opcodes this emulator doesn't support ({unsupported}) \
+             and will stop if it reaches them",
+            args.rom.display()
+        );
+    }
+    Ok((args.options(&config, &rom)?, rom))
+}
+
+fn build_runner(options: &RunOptions, rom: &[u8]) -> Result<Runner, Box<dyn std::error::Error>> {
+    let rng = Rng::new(options.rng, options.seed.unwrap_or_else(rand::random));
+    let mut chip = chip8::Chip8::default()
+        .with_rng(rng)
+        .with_quirks(options.quirks());
+    chip.load_rom(rom)?;
+    let mut runner = Runner::new(chip, options.ipf);
+    if let Some(path) = &options.trace {
+        runner = runner.with_trace(Box::new(io::BufWriter::new(std::fs::File::create(path)?)));
+    }
+    if options.profile.is_some() {
+        runner = runner.with_profiler();
+    }
+    Ok(runner.with_cheats(Cheats::default().with_overrides(&options.cheats)))
+}
+
+/// Writes the profiler report to `--profile` and the folded stacks beside it.
+fn write_profile(options: &RunOptions, runner: &Runner, rom: &[u8]) -> io::Result<()> {
+    if let (Some(path), Some(profiler)) = (&options.profile, runner.profiler()) {
+        std::fs::write(path, profiler.report(romdb::program(rom)))?;
+        std::fs::write(path.with_extension("folded"), profiler.folded())?;
+    }
+    Ok(())
+}
+
+fn run_headless(options: &RunOptions, rom: &[u8]) -> Result<Runner, Box<dyn std::error::Error>> {
+    let mut runner = build_runner(options, rom)?;
+    let outcome = runner.run_headless(options.frames.unwrap_or(DEFAULT_HEADLESS_FRAMES))?;
+    if let RunOutcome::Halted { pc, frames } = outcome {
+        eprintln!("halted at 0x{pc:03X} after {frames} frames");
+    }
+    write_profile(options, &runner, rom)?;
+    Ok(runner)
+}
+
+/// The display as text, one line per pixel row.
+fn frame_text(display: &DisplayBuffer) -> String {
+    Renderer::Block
+        .render(display)
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.ch).collect::<String>() + "\n")
+        .collect()
+}
+
+fn test(
+    options: &RunOptions,
+    rom: &[u8],
+    expect: Option<&Path>,
+    update: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let runner = run_headless(options, rom)?;
+    let display = &runner.chip.display_buffer;
+    let Some(expect) = expect else {
+        print!("{}", frame_text(display));
+        return Ok(());
+    };
+    if update {
+        image::save_image(expect, display, 1, &Palette::CLASSIC)?;
+        println!("updated {}", expect.display());
+        return Ok(());
+    }
+    let golden = image::read_pbm(
+        &std::fs::read(expect).map_err(|e| format!("failed to read {}: {e}", expect.display()))?,
+    )?;
+    let matches = golden.len() == DisplayBuffer::HEIGHT
+        && golden
+            .iter()
+            .zip(display.pixels.iter())
+            .all(|(expected, actual)| {
+                expected
+                    .iter()
+                    .copied()
+                    .eq(actual.iter().map(|index| *index != 0))
+            });
+    if !matches {
+        print!("{}", frame_text(display));
+        return Err(format!("final frame differs from {}", expect.display()).into());
+    }
+    println!("ok");
+    Ok(())
+}
+
+fn info(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let rom = std::fs::read(path).map_err(|e| format!("failed to read rom: {e}"))?;
+    let program = romdb::program(&rom);
+    let available = Memory::MEMORY_SIZE - Memory::PROGRAM_START as usize;
+    println!("file: {}", path.display());
+    println!("sha1: {}", sha1::sha1_hex(&rom));
+    let known = romdb::lookup(&rom);
+    match &known {
+        Some(rom) => {
+            let by = rom.author.as_ref().map(|author| format!(" by {author}"));
+            let year = rom.year.map(|year| format!(" ({year})"));
+            println!(
+                "database: {}{}{}",
+                rom.title,
+                by.unwrap_or_default(),
+                year.unwrap_or_default()
+            );
+        }
+        None => println!("database: no match"),
+    }
+    println!(
+        "size: {} bytes ({:.1}% of {available} available)",
+        program.len(),
+        program.len() as f64 * 100.0 / available as f64
+    );
+    if program.len() > available {
+        println!("warning: too large to load");
+    }
+
+    let analysis = Analysis::new(program);
+    let code = analysis.code_bytes();
+    println!(
+        "code: {code} bytes reachable ({:.0}%), {} bytes data or unreached",
+        code as f64 * 100.0 / program.len().max(1) as f64,
+        program.len().saturating_sub(code)
+    );
+    if analysis.extensions.is_empty() {
+        println!("extensions: none");
+    }
+    for (op, addr) in &analysis.extensions {
+        let note = match op.implemented() {
+            true => "",
+            false => ", not supported",
+        };
+        println!(
+            "extension: {} {op} (first at 0x{addr:03X}){note}",
+            op.extension()
+        );
+    }
+    for (addr, target) in &analysis.machine_calls {
+        println!("machine code call: 0x{addr:03X} -> 0x{target:03X}");
+    }
+    for addr in analysis.indirect_jumps.keys() {
+        println!("indirect jump: 0x{addr:03X}, code after it may be missed");
+    }
+    let database_platform = known
+        .iter()
+        .flat_map(|rom| &rom.flags)
+        .find(|(flag, _)| flag == "--platform")
+        .map(|(_, platform)| platform.as_str());
+    match database_platform {
+        Some(platform) => println!(
+            "platform: {platform} (database), {} (from opcodes)",
+            analysis.suggested_platform()
+        ),
+        None => println!(
+            "platform: {} (suggested from opcodes)",
+            analysis.suggested_platform()
+        ),
+    }
+    Ok(())
+}
+
+fn run(options: RunOptions, rom: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    // Cheats saved from the debugger apply to interactive runs only, keeping
+    // headless runs reproducible
+    let cheats = Cheats::load(&sha1::sha1_hex(rom))?.with_overrides(&options.cheats);
+    let mut runner = build_runner(&options, rom)?.with_cheats(cheats);
+    let mut debugger = Debugger::new(options.paused);
+
+    let mut audio: Box<dyn AudioSink> = match options.audio.as_str() {
+        "bell" => Box::new(BellSink::default()),
+        "none" => Box::new(NullSink),
+        mode => match mode.strip_prefix("wav:") {
+            Some(path) => Box::new(WavSink::new(
+                io::BufWriter::new(std::fs::File::create(path)?),
+                options.tone,
+            )?),
+            None => return Err(format!("unknown audio mode: {mode}").into()),
+        },
+    };
+
+    let phosphor = matches!(options.flicker, Some(FlickerMode::Phosphor(_)));
+    let mut graphics = options.graphics;
+    if options.renderer.is_none() && graphics.is_none() && !phosphor {
+        graphics = GraphicsProtocol::detect();
+    }
+    let renderer = match options.renderer {
+        Some(renderer) => renderer,
+        None => {
+            let (cols, rows) = crossterm::terminal::size()?;
+            Renderer::auto(cols, rows, DisplayBuffer::WIDTH, DisplayBuffer::HEIGHT)
+        }
+    };
+    if phosphor && (graphics.is_some() || !renderer.shades()) {
+        return Err("phosphor flicker reduction needs the block or double renderer".into());
+    }
+
+    let scale = options.scale;
+    let mut frontend = TerminalFrontend::new(renderer);
+    let custom_palette = options.palette.is_some() || !options.colors.is_empty();
+    let mut palette = options.palette.unwrap_or(Palette::CLASSIC);
+    for (index, rgb) in &options.colors {
+        palette.0[*index] = *rgb;
+    }
+    if custom_palette {
+        frontend = frontend.with_palette(palette, options.color_mode);
+    }
+    if let Some(mode) = options.flicker {
+        frontend = frontend.with_flicker(mode);
+    }
+    if let Some(protocol) = graphics {
+        frontend = frontend.with_graphics(protocol, scale);
+    }
+
+    let start_recording = |path: &Path| {
+        GifRecorder::new(
+            io::BufWriter::new(std::fs::File::create(path)?),
+            DisplayBuffer::WIDTH,
+            DisplayBuffer::HEIGHT,
+            scale,
+            &palette,
+        )
+    };
+    let mut recorder = options.record.as_deref().map(start_recording).transpose()?;
 
     let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen, Hide)?;
-    enable_raw_mode()?;
+    let mut screen: Box<dyn Write> = match &options.cast {
+        Some(path) => {
+            let (cols, rows) = crossterm::terminal::size()?;
+            Box::new(CastWriter::new(
+                io::stdout(),
+                io::BufWriter::new(std::fs::File::create(path)?),
+                cols,
+                rows,
+            )?)
+        }
+        None => Box::new(io::stdout()),
+    };
+    let terminal = TerminalGuard::enter()?;
 
     let mut quit = false;
-    let mut keypad = Keypad::default();
-    let mut last_display_buffer_refresh = Instant::now();
-    while !quit {
-        let loop_time = Instant::now();
-        if loop_time - last_display_buffer_refresh > Duration::from_secs_f32(FRAME_TIMEOUT) {
-            render_to_screen(&chip.display_buffer)?;
-            last_display_buffer_refresh = loop_time;
+    let mut frames = 0;
+    let mut keypad = Keypad::default().with_keymap(options.keymap);
+    let frame_time = Duration::from_secs_f32(FRAME_TIMEOUT);
+    while !quit && options.frames.is_none_or(|limit| frames < limit) {
+        let frame_start = Instant::now();
+        if debugger.paused() {
+            // Recording costs time on every instruction, so only start once
+            // the debugger is in use
+            runner.start_history();
+            frontend.render_to_screen(&mut screen, &mut runner.chip.display_buffer)?;
+            draw_panel(&mut screen, &debugger.panel(&runner.chip), "(debug) ")?;
+            let line = read_command(&mut stdout)?;
+            match debugger.execute(&line, &mut runner, &keypad) {
+                DebuggerAction::Quit => quit = true,
+                DebuggerAction::Resume => {
+                    execute!(screen, Clear(ClearType::All))?;
+                    frontend.invalidate();
+                    runner.chip.display_buffer.mark_dirty();
+                }
+                DebuggerAction::Stay => {}
+            }
+            continue;
+        }
+
+        match keypad.poll()? {
+            Some(Hotkey::Quit) => quit = true,
+            Some(Hotkey::Screenshot) => {
+                let save = || -> Result<String, Box<dyn std::error::Error>> {
+                    let path = timestamped_path(&options.screenshot_format)?;
+                    runner.chip.screenshot(Path::new(&path), scale, &palette)?;
+                    Ok(format!("screenshot saved to {path}"))
+                };
+                show_status(&mut screen, save())?;
+            }
+            Some(Hotkey::ToggleRecording) => {
+                let mut toggle = || -> Result<String, Box<dyn std::error::Error>> {
+                    match recorder.take() {
+                        Some(recorder) => {
+                            recorder.finish()?;
+                            Ok("recording stopped".to_string())
+                        }
+                        None => {
+                            let path = timestamped_path("gif")?;
+                            recorder = Some(start_recording(Path::new(&path))?);
+                            Ok(format!("recording to {path}"))
+                        }
+                    }
+                };
+                show_status(&mut screen, toggle())?;
+            }
+            Some(Hotkey::Pause) => debugger.pause(),
+            None => {}
+        }
+        debug_out(keypad.pressed());
+
+        runner.frame(&keypad, &mut debugger)?;
+        if debugger.paused() {
+            continue;
+        }
+        frames += 1;
+        frontend.render_to_screen(&mut screen, &mut runner.chip.display_buffer)?;
+        if let Some(recorder) = &mut recorder {
+            recorder.frame(&runner.chip.display_buffer)?;
         }
-        quit = keypad.poll()?;
-        debug_out(&keypad.pressed());
-        chip.cycle(&keypad)?;
+        audio.frame(runner.chip.audio_frame())?;
         // keypad.clear();
+        std::thread::sleep(frame_time.saturating_sub(frame_start.elapsed()));
     }
 
-    disable_raw_mode()?;
-    execute!(stdout, LeaveAlternateScreen, Show)?;
-    stdout.flush()?;
+    runner.finish()?;
+    audio.finish()?;
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    screen.flush()?;
+    drop(terminal);
+    write_profile(&options, &runner, rom)?;
 
     Ok(())
 }
 
-pub fn render_to_screen(display_buffer: &DisplayBuffer) -> Result<(), io::Error> {
-    let mut stdout = io::stdout();
-    for y in 0..display_buffer.pixels.len() {
-        for x in 0..display_buffer.pixels[y].len() {
-            let char = match display_buffer.pixels[y][x] {
-                true => '█',
-                false => ' ',
-            };
-            queue!(
-                stdout,
-                cursor::MoveTo(x as u16, y as u16),
-                style::Print(char)
-            )?;
-        }
+/// Raw mode on the alternate screen with the cursor hidden, undone when
+/// dropped so the terminal is restored however the session ends.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        enable_raw_mode()?;
+        Ok(Self)
     }
-    stdout.flush()?;
-    Ok(())
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+    }
+}
+
+/// Shows how a hotkey went on the bottom line. Failures are shown rather
+/// than returned, so a bad capture doesn't end the session.
+fn show_status<W: Write>(
+    out: &mut W,
+    result: Result<String, Box<dyn std::error::Error>>,
+) -> io::Result<()> {
+    let message = result.unwrap_or_else(|e| format!("error: {e}"));
+    draw_panel(out, &[], &message)
+}
+
+/// Reads a debugger command with the terminal temporarily back in line mode.
+fn read_command(stdout: &mut io::Stdout) -> io::Result<String> {
+    disable_raw_mode()?;
+    execute!(stdout, Show)?;
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        // stdin closed, nothing more will come
+        line = "quit".to_string();
+    }
+    execute!(stdout, Hide)?;
+    enable_raw_mode()?;
+    Ok(line)
+}
+
+/// File name for hotkey captures, unique per millisecond.
+fn timestamped_path(extension: &str) -> Result<String, std::time::SystemTimeError> {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_millis();
+    Ok(format!("chip8-{millis}.{extension}"))
 }
@@ -1,11 +1,26 @@
+use std::cell::RefCell;
+
 use crate::error::{Error, Result};
 use crate::font::FONT;
 
-#[derive(Debug, Clone, Copy)]
-pub struct Memory([u8; Self::MEMORY_SIZE]);
+/// A data read or write made by an instruction, for debugger watchpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub addr: u16,
+    pub write: bool,
+    pub value: u8,
+    /// The byte before a write, for undoing it.
+    pub previous: u8,
+}
+
+/// RAM plus, once turned on with `set_logging`, a log of the `read`s and
+/// `write`s made since the last `take_accesses`. Instruction fetches and ROM
+/// loading aren't logged.
+#[derive(Debug, Clone)]
+pub struct Memory([u8; Self::MEMORY_SIZE], RefCell<Option<Vec<Access>>>);
 
 impl Memory {
-    const MEMORY_SIZE: usize = 4096;
+    pub const MEMORY_SIZE: usize = 4096;
     pub const FONT_START: u16 = 0x050;
     pub const PROGRAM_START: u16 = 0x200;
 
@@ -31,10 +46,18 @@ impl Memory {
 
     pub fn read<A: Into<usize>>(&self, addr: A) -> Result<u8> {
         let addr = addr.into();
-        self.0
+        let value = self
+            .0
             .get(addr)
             .copied()
-            .ok_or_else(|| Error::Unknown(format!("memory read out of bounds: {addr}")))
+            .ok_or_else(|| Error::Unknown(format!("memory read out of bounds: {addr}")))?;
+        self.log(Access {
+            addr: addr as u16,
+            write: false,
+            value,
+            previous: value,
+        });
+        Ok(value)
     }
 
     pub fn write<A: Into<usize>>(&mut self, addr: A, value: u8) -> Result<()> {
@@ -42,16 +65,69 @@ impl Memory {
         let cell = self
             .0
             .get_mut(addr)
-            .ok_or_else(|| Error::Unknown(format!("memory read out of bounds: {addr}")))?;
-        *cell = value;
+            .ok_or_else(|| Error::Unknown(format!("memory write out of bounds: {addr}")))?;
+        let previous = std::mem::replace(cell, value);
+        self.log(Access {
+            addr: addr as u16,
+            write: true,
+            value,
+            previous,
+        });
         Ok(())
     }
 
+    /// Reads `len` bytes without logging them, for debuggers.
+    pub fn peek<A: Into<usize>>(&self, addr: A, len: usize) -> Result<&[u8]> {
+        let addr = addr.into();
+        self.0
+            .get(addr..addr + len)
+            .ok_or_else(|| Error::Unknown(format!("memory read out of bounds: {addr}")))
+    }
+
+    /// Starts or stops logging accesses. Stopping drops the log.
+    pub fn set_logging(&mut self, on: bool) {
+        let log = self.1.get_mut();
+        match on {
+            true => _ = log.get_or_insert_default(),
+            false => *log = None,
+        }
+    }
+
+    fn log(&self, access: Access) {
+        if let Some(log) = self.1.borrow_mut().as_mut() {
+            log.push(access);
+        }
+    }
+
+    pub fn take_accesses(&self) -> Vec<Access> {
+        self.1
+            .borrow_mut()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// How many accesses are logged, to find later ones with `accesses_since`.
+    pub fn access_count(&self) -> usize {
+        self.1.borrow().as_ref().map_or(0, Vec::len)
+    }
+
+    pub fn accesses_since(&self, count: usize) -> Vec<Access> {
+        self.1
+            .borrow()
+            .as_ref()
+            .and_then(|log| log.get(count..))
+            .unwrap_or_default()
+            .to_vec()
+    }
+
     pub fn write_slice<A: Into<usize>>(&mut self, addr: A, data: &[u8]) -> Result<()> {
         let addr = addr.into();
         let end = addr + data.len();
         if end > Self::MEMORY_SIZE {
-            return Err(Error::Unknown(format!("memory read out of bounds: {addr}")));
+            return Err(Error::Unknown(format!(
+                "memory write out of bounds: {addr}"
+            )));
         }
         self.0[addr..end].copy_from_slice(data);
         Ok(())
@@ -60,7 +136,7 @@ impl Memory {
 
 impl Default for Memory {
     fn default() -> Self {
-        let mut memory = Self([0; Self::MEMORY_SIZE]);
+        let mut memory = Self([0; Self::MEMORY_SIZE], RefCell::default());
         memory.0[Self::FONT_START as usize..Self::FONT_START as usize + FONT.len()]
             .copy_from_slice(&FONT);
         memory
@@ -70,6 +146,12 @@ impl Default for Memory {
 #[derive(Debug, Clone, Copy)]
 pub struct OpCode(u16);
 
+impl From<u16> for OpCode {
+    fn from(value: u16) -> Self {
+        Self(value)
+    }
+}
+
 impl OpCode {
     pub fn inner(&self) -> u16 {
         self.0
@@ -0,0 +1,125 @@
+use crossterm::style::Stylize;
+
+use crate::{
+    chip8::Chip8,
+    error::{Error, Result},
+    font::FONT,
+    memory::Memory,
+    renderer::BRAILLE_DOTS,
+};
+
+/// Bytes per hex view row.
+const ROW: usize = 16;
+
+/// `rows` lines of hex dump from the row holding `start`, with ASCII and bit
+/// pattern columns. Recent writes are yellow, the instruction at PC reversed, the byte
+/// at I underlined and the font dimmed.
+pub fn hex_view(chip: &Chip8, start: u16, rows: usize, recent: &[u16]) -> Vec<String> {
+    let first = start as usize / ROW * ROW;
+    let font = Memory::FONT_START as usize..Memory::FONT_START as usize + FONT.len();
+    let pc = chip.pc() as usize;
+    let index = chip.index() as usize;
+    (first..Memory::MEMORY_SIZE)
+        .step_by(ROW)
+        .take(rows)
+        .map(|row| {
+            let bytes = chip.read_memory(row as u16, ROW).unwrap_or_default();
+            let hex = bytes
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| {
+                    let addr = row + i;
+                    let text = format!("{byte:02X}");
+                    if recent.contains(&(addr as u16)) {
+                        text.yellow().to_string()
+                    } else if (pc..pc + 2).contains(&addr) {
+                        text.reverse().to_string()
+                    } else if addr == index {
+                        text.underlined().to_string()
+                    } else if font.contains(&addr) {
+                        text.dim().to_string()
+                    } else {
+                        text
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = bytes
+                .iter()
+                .map(|b| match b.is_ascii_graphic() || *b == b' ' {
+                    true => *b as char,
+                    false => '.',
+                })
+                .collect::<String>();
+            let bits = bytes.iter().map(|b| bit_pattern(*b)).collect::<String>();
+            format!("0x{row:03X}  {hex}  {ascii}  {bits}")
+        })
+        .collect()
+}
+
+/// A byte as one braille cell, the high nibble down the left dots and the
+/// low nibble down the right, so sprite data stands out in a hex dump.
+fn bit_pattern(byte: u8) -> char {
+    let dots = BRAILLE_DOTS
+        .iter()
+        .filter(|(dx, dy, _)| byte >> (7 - dx * 4 - dy) & 1 == 1)
+        .fold(0, |dots, (_, _, bit)| dots | bit);
+    char::from_u32(0x2800 + dots).unwrap_or(' ')
+}
+
+/// The `n` bytes at I as the pixels `DXYN` would draw, one row per line.
+/// `DXY0` draws nothing, so `n` must be at least 1.
+pub fn sprite_preview(chip: &Chip8, n: usize) -> Result<Vec<String>> {
+    if !(1..=15).contains(&n) {
+        return Err(Error::Unknown(format!("sprites are 1 to 15 rows: {n}")));
+    }
+    let index = chip.index();
+    let len = n.min(Memory::MEMORY_SIZE.saturating_sub(index as usize));
+    let bytes = chip.read_memory(index, len).unwrap_or_default();
+    Ok(bytes
+        .iter()
+        .enumerate()
+        .map(|(row, byte)| {
+            let pixels = (0..8)
+                .rev()
+                .map(|bit| if byte >> bit & 1 == 1 { '█' } else { '·' })
+                .collect::<String>();
+            format!("0x{:03X}  {byte:02X}  {pixels}", index as usize + row)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_view() {
+        let mut chip = Chip8::default();
+        chip.load_rom(b"\x00\xE0Hi!").unwrap();
+        let lines = hex_view(&chip, 0x205, 2, &[]);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("0x200  "));
+        assert!(lines[0].contains("  ..Hi!...........  \u{2800}\u{2807}"));
+        assert_eq!(bit_pattern(0xF0), '\u{2847}');
+        assert_eq!(bit_pattern(0x81), '\u{2881}');
+        assert!(lines[0].contains(&"00".reverse().to_string()));
+        assert!(lines[1].starts_with("0x210  00 00"));
+        assert!(hex_view(&chip, 0x200, 1, &[0x202])[0].contains(&"48".yellow().to_string()));
+        assert_eq!(hex_view(&chip, 0xFF0, 4, &[]).len(), 1);
+    }
+
+    #[test]
+    fn test_sprite_preview() {
+        let mut chip = Chip8::default();
+        chip.set_index_register(Memory::FONT_START);
+        assert_eq!(
+            sprite_preview(&chip, 2).unwrap(),
+            [
+                format!("0x{:03X}  F0  ████····", Memory::FONT_START),
+                format!("0x{:03X}  90  █··█····", Memory::FONT_START + 1),
+            ]
+        );
+        assert!(sprite_preview(&chip, 0).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use crossterm::style::Color;
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl std::str::FromStr for Rgb {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let value = (hex.len() == 6)
+            .then(|| u32::from_str_radix(hex, 16).ok())
+            .flatten()
+            .ok_or_else(|| Error::Unknown(format!("bad colour, expected #RRGGBB: {s}")))?;
+        Ok(Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8))
+    }
+}
+
+/// Colours indexed by pixel value: 0 is the background, 1 and 2 the two
+/// XO-CHIP bitplanes and 3 pixels lit on both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette(pub [Rgb; 4]);
+
+impl Palette {
+    pub const CLASSIC: Palette = Palette([
+        Rgb(0x00, 0x00, 0x00),
+        Rgb(0xFF, 0xFF, 0xFF),
+        Rgb(0xAA, 0xAA, 0xAA),
+        Rgb(0x55, 0x55, 0x55),
+    ]);
+    pub const GREEN_PHOSPHOR: Palette = Palette([
+        Rgb(0x0A, 0x14, 0x0A),
+        Rgb(0x33, 0xFF, 0x66),
+        Rgb(0x1A, 0x99, 0x3D),
+        Rgb(0x99, 0xFF, 0xB3),
+    ]);
+    pub const AMBER: Palette = Palette([
+        Rgb(0x1A, 0x10, 0x00),
+        Rgb(0xFF, 0xB0, 0x00),
+        Rgb(0x99, 0x66, 0x00),
+        Rgb(0xFF, 0xDD, 0x88),
+    ]);
+    pub const OCTO: Palette = Palette([
+        Rgb(0x99, 0x66, 0x00),
+        Rgb(0xFF, 0xCC, 0x00),
+        Rgb(0xFF, 0x66, 0x00),
+        Rgb(0x66, 0x22, 0x00),
+    ]);
+    pub const LCD: Palette = Palette([
+        Rgb(0x9B, 0xBC, 0x0F),
+        Rgb(0x0F, 0x38, 0x0F),
+        Rgb(0x30, 0x62, 0x30),
+        Rgb(0x8B, 0xAC, 0x0F),
+    ]);
+
+    pub fn named(name: &str) -> Result<Self> {
+        match name {
+            "classic" => Ok(Self::CLASSIC),
+            "green" => Ok(Self::GREEN_PHOSPHOR),
+            "amber" => Ok(Self::AMBER),
+            "octo" => Ok(Self::OCTO),
+            "lcd" => Ok(Self::LCD),
+            _ => Err(Error::Unknown(format!("unknown palette: {name}"))),
+        }
+    }
+
+    pub fn color(&self, index: u8) -> Rgb {
+        self.0[index as usize & 0x3]
+    }
+}
+
+/// How colours are sent to the terminal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    #[default]
+    TrueColor,
+    Ansi256,
+}
+
+impl ColorMode {
+    /// Truecolor when the terminal advertises it through `COLORTERM`.
+    pub fn detect() -> Self {
+        match std::env::var("COLORTERM").as_deref() {
+            Ok("truecolor") | Ok("24bit") => ColorMode::TrueColor,
+            _ => ColorMode::Ansi256,
+        }
+    }
+
+    pub fn color(&self, Rgb(r, g, b): Rgb) -> Color {
+        match self {
+            ColorMode::TrueColor => Color::Rgb { r, g, b },
+            ColorMode::Ansi256 => Color::AnsiValue(ansi256(Rgb(r, g, b))),
+        }
+    }
+}
+
+/// Nearest entry in the xterm 6x6x6 colour cube or grey ramp.
+fn ansi256(Rgb(r, g, b): Rgb) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest = |c: u8| {
+        (0..6)
+            .min_by_key(|&i| (LEVELS[i] as i32 - c as i32).abs())
+            .unwrap_or(0)
+    };
+    let (ri, gi, bi) = (nearest(r), nearest(g), nearest(b));
+    let cube = (LEVELS[ri], LEVELS[gi], LEVELS[bi]);
+    let grey_index = ((r as u32 + g as u32 + b as u32) / 3).saturating_sub(8) / 10;
+    let grey_index = grey_index.min(23) as u8;
+    let grey = 8 + grey_index * 10;
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        [(r, cr), (g, cg), (b, cb)]
+            .iter()
+            .map(|&(a, b)| (a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+    if distance((grey, grey, grey)) < distance(cube) {
+        232 + grey_index
+    } else {
+        16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rgb() {
+        assert_eq!("#FF8000".parse::<Rgb>().unwrap(), Rgb(0xFF, 0x80, 0x00));
+        assert_eq!("33ff66".parse::<Rgb>().unwrap(), Rgb(0x33, 0xFF, 0x66));
+        assert!("#FFF".parse::<Rgb>().is_err());
+    }
+
+    #[test]
+    fn test_ansi256() {
+        assert_eq!(ansi256(Rgb(0, 0, 0)), 16);
+        assert_eq!(ansi256(Rgb(255, 255, 255)), 231);
+        assert_eq!(ansi256(Rgb(255, 0, 0)), 196);
+        assert_eq!(ansi256(Rgb(128, 128, 128)), 244);
+    }
+}
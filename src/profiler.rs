@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::{
+    analysis::{Analysis, EdgeKind, subroutine_name},
+    disasm::instruction_line,
+    memory::{Memory, OpCode},
+};
+
+/// How many hot spots and loops the report lists.
+const TOP: usize = 10;
+
+/// Execution counts gathered while running, for finding hot spots and the
+/// code a play session never reached.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    /// Executions of the instruction at each address.
+    counts: BTreeMap<u16, u64>,
+    /// Executions per opcode class, such as `DXYN`.
+    classes: BTreeMap<String, u64>,
+    /// Instructions executed under each call stack of subroutine entries.
+    stacks: BTreeMap<Vec<u16>, u64>,
+    /// Where each `BNNN` jump went, to follow them in the static analysis.
+    indirect: BTreeMap<u16, BTreeSet<u16>>,
+    last_indirect: Option<u16>,
+    frames: u64,
+}
+
+impl Profiler {
+    /// Counts one instruction about to execute at `pc`.
+    pub fn record(&mut self, pc: u16, opcode: OpCode, call_stack: Vec<u16>) {
+        if let Some(from) = self.last_indirect.take() {
+            self.indirect.entry(from).or_default().insert(pc);
+        }
+        if opcode.code() == 0xB {
+            self.last_indirect = Some(pc);
+        }
+        *self.counts.entry(pc).or_default() += 1;
+        *self.classes.entry(class(opcode)).or_default() += 1;
+        *self.stacks.entry(call_stack).or_default() += 1;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Call stacks in the folded format read by flamegraph tools: one
+    /// `main;sub_2A0;sub_2F4 count` line per stack.
+    pub fn folded(&self) -> String {
+        let mut out = String::new();
+        for (stack, count) in &self.stacks {
+            let names = std::iter::once(Memory::PROGRAM_START)
+                .chain(stack.iter().copied())
+                .map(subroutine_name)
+                .collect::<Vec<_>>();
+            let _ = writeln!(out, "{} {count}", names.join(";"));
+        }
+        out
+    }
+
+    /// A text report of where time went in `program`.
+    pub fn report(&self, program: &[u8]) -> String {
+        let total = self.counts.values().sum::<u64>();
+        let frames = self.frames.max(1) as f64;
+        let percent = |count: u64| count as f64 * 100.0 / total.max(1) as f64;
+        let analysis = Analysis::with_indirect_targets(program, self.indirect.clone());
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "instructions: {total} over {} frames ({:.1} per frame)",
+            self.frames,
+            total as f64 / frames
+        );
+
+        let _ = writeln!(out, "\nopcode classes:");
+        let mut classes = self.classes.iter().collect::<Vec<_>>();
+        classes.sort_by(|a, b| b.1.cmp(a.1));
+        for (class, count) in classes {
+            let _ = writeln!(out, "  {class:<6} {count:>10}  {:5.1}%", percent(*count));
+        }
+
+        let _ = writeln!(out, "\nhot spots:");
+        let mut hot = self.counts.iter().collect::<Vec<_>>();
+        hot.sort_by(|a, b| b.1.cmp(a.1));
+        for (addr, count) in hot.into_iter().take(TOP) {
+            let line = match analysis.instructions.contains_key(addr) {
+                true => instruction_line(program, *addr),
+                false => format!("0x{addr:03X}"),
+            };
+            let _ = writeln!(out, "  {line:<40} {count:>10}  {:5.1}%", percent(*count));
+        }
+
+        // A loop is the range from the target of a backward branch to the
+        // branch itself
+        let _ = writeln!(out, "\nhottest loops:");
+        let mut loops = analysis
+            .edges
+            .iter()
+            .flat_map(|(addr, edges)| {
+                edges
+                    .iter()
+                    .filter(move |(to, kind)| *kind != EdgeKind::Call && to <= addr)
+                    .map(move |(to, _)| (*to, *addr))
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|(start, end)| {
+                let count = self.counts.range(start..=end).map(|(_, c)| c).sum::<u64>();
+                (count, start, end)
+            })
+            .filter(|(count, ..)| *count > 0)
+            .collect::<Vec<_>>();
+        loops.sort_by_key(|(count, ..)| std::cmp::Reverse(*count));
+        if loops.is_empty() {
+            let _ = writeln!(out, "  none");
+        }
+        for (count, start, end) in loops.into_iter().take(TOP) {
+            let _ = writeln!(
+                out,
+                "  0x{start:03X}-0x{end:03X}  {count:>10}  {:5.1}%",
+                percent(count)
+            );
+        }
+
+        let _ = writeln!(out, "\nsubroutines (instructions per frame):");
+        let _ = writeln!(out, "  {:<10} {:>8} {:>8}", "", "self", "total");
+        let entries = std::iter::once(Memory::PROGRAM_START)
+            .chain(self.stacks.keys().flatten().copied())
+            .collect::<BTreeSet<_>>();
+        for entry in entries {
+            let (mut own, mut inclusive) = (0, 0);
+            for (stack, count) in &self.stacks {
+                let innermost = stack.last().copied().unwrap_or(Memory::PROGRAM_START);
+                if innermost == entry {
+                    own += count;
+                }
+                if entry == Memory::PROGRAM_START || stack.contains(&entry) {
+                    inclusive += count;
+                }
+            }
+            let _ = writeln!(
+                out,
+                "  {:<10} {:>8.1} {:>8.1}",
+                subroutine_name(entry),
+                own as f64 / frames,
+                inclusive as f64 / frames
+            );
+        }
+
+        let _ = writeln!(out, "\nuncovered code:");
+        // Runs of unexecuted instructions: first, last, the address after and
+        // how many
+        let mut ranges = Vec::<(u16, u16, u16, usize)>::new();
+        for (&addr, &len) in &analysis.instructions {
+            if self.counts.contains_key(&addr) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some((_, last, next, instructions)) if *next == addr => {
+                    (*last, *next) = (addr, addr + len);
+                    *instructions += 1;
+                }
+                _ => ranges.push((addr, addr, addr + len, 1)),
+            }
+        }
+        if ranges.is_empty() {
+            let _ = writeln!(out, "  none");
+        }
+        for (first, last, _, instructions) in ranges {
+            let _ = writeln!(
+                out,
+                "  0x{first:03X}-0x{last:03X}  {instructions} instructions"
+            );
+        }
+        out
+    }
+}
+
+/// The opcode pattern an instruction belongs to, such as `8XY4` or `FX33`.
+fn class(opcode: OpCode) -> String {
+    let (code, x, n) = (opcode.code(), opcode.x(), opcode.n());
+    match code {
+        0x0 if opcode.inner() & 0xFFE0 == 0x00C0 => format!("00{:X}N", opcode.nn() >> 4),
+        0x0 if x == 0 => format!("{:04X}", opcode.inner()),
+        0x0 | 0x1 | 0x2 | 0xA | 0xB => format!("{code:X}NNN"),
+        0x3 | 0x4 | 0x6 | 0x7 | 0xC => format!("{code:X}XNN"),
+        0x5 | 0x8 | 0x9 => format!("{code:X}XY{n:X}"),
+        0xD => "DXYN".to_string(),
+        _ if opcode.inner() == 0xF000 => "F000".to_string(),
+        _ => format!("{code:X}X{:02X}", opcode.nn()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let program = [
+            0x22, 0x08, // 0x200: CALL 0x208
+            0x31, 0x05, // 0x202: SE V1, 0x05
+            0x12, 0x00, // 0x204: JP 0x200
+            0x00, 0xE0, // 0x206: CLS, never run
+            0x71, 0x01, // 0x208: ADD V1, 0x01
+            0x00, 0xEE, // 0x20A: RET
+        ];
+        let mut profiler = Profiler::default();
+        for _ in 0..2 {
+            profiler.record(0x200, OpCode::from(0x2208), vec![]);
+            profiler.record(0x208, OpCode::from(0x7101), vec![0x208]);
+            profiler.record(0x20A, OpCode::from(0x00EE), vec![0x208]);
+            profiler.record(0x202, OpCode::from(0x3105), vec![]);
+            profiler.record(0x204, OpCode::from(0x1200), vec![]);
+            profiler.end_frame();
+        }
+        assert_eq!(profiler.folded(), "main 6\nmain;sub_208 4\n");
+        let report = profiler.report(&program);
+        assert!(report.starts_with("instructions: 10 over 2 frames (5.0 per frame)"));
+        assert!(report.contains("  7XNN            2   20.0%"));
+        assert!(report.contains("  0x200-0x204           6   60.0%"));
+        assert!(report.contains("  main            3.0      5.0"));
+        assert!(report.contains("  sub_208         2.0      2.0"));
+        assert!(report.contains("  0x206-0x206  1 instructions"));
+    }
+
+    #[test]
+    fn test_class() {
+        let classes = [0x00E0, 0x00C4, 0x0123, 0x8AB4, 0xD125, 0xF233, 0xF000]
+            .map(|op| class(OpCode::from(op)));
+        assert_eq!(
+            classes,
+            ["00E0", "00CN", "0NNN", "8XY4", "DXYN", "FX33", "F000"]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::error::{Error, Result};
+
+/// Behaviours that differ between CHIP-8 implementations. The defaults match
+/// what this interpreter has always done.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `DXYN` stalls until the next 60 Hz interrupt before drawing, capping
+    /// draws at one per frame as on the COSMAC VIP.
+    pub display_wait: bool,
+    /// Sprite pixels past the right or bottom edge wrap around to the opposite
+    /// side instead of being clipped.
+    pub wrap_sprites: bool,
+}
+
+impl Quirks {
+    /// Turns a single quirk on or off by its field name.
+    pub fn set(&mut self, name: &str, on: bool) -> Result<()> {
+        match name {
+            "display_wait" => self.display_wait = on,
+            "wrap_sprites" => self.wrap_sprites = on,
+            _ => return Err(Error::Unknown(format!("unknown quirk: {name}"))),
+        }
+        Ok(())
+    }
+}
+
+/// Named quirk profiles for the common target platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// The original interpreter on the COSMAC VIP.
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks {
+                display_wait: true,
+                wrap_sprites: false,
+            },
+            Platform::SuperChip => Quirks {
+                display_wait: false,
+                wrap_sprites: false,
+            },
+            Platform::XoChip => Quirks {
+                display_wait: false,
+                wrap_sprites: true,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        })
+    }
+}
+
+impl std::str::FromStr for Platform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "chip8" | "vip" => Ok(Platform::Chip8),
+            "schip" | "superchip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(Error::Unknown(format!("unknown platform: {s}"))),
+        }
+    }
+}
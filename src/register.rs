@@ -1,9 +1,15 @@
 #[macro_export]
 macro_rules! new_register {
     ($name:ident, $size:ty) => {
-        #[derive(Debug, Default, Clone, Copy)]
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
         pub struct $name($size);
 
+        impl From<$size> for $name {
+            fn from(value: $size) -> Self {
+                Self(value)
+            }
+        }
+
         impl $name {
             pub fn get(&self) -> $size {
                 self.0
@@ -19,7 +25,7 @@ macro_rules! new_register {
 new_register!(Register8Bit, u8);
 new_register!(Register16Bit, u16);
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Register8BitArray([Register8Bit; 16]);
 
 impl Register8BitArray {
@@ -0,0 +1,188 @@
+use crate::display::PixelSource;
+use crate::error::{Error, Result};
+
+/// How CHIP-8 pixels are packed into terminal character cells.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Renderer {
+    /// One `█` per pixel.
+    #[default]
+    Block,
+    /// Two `█` per pixel, roughly square pixels in most terminal fonts.
+    DoubleBlock,
+    /// `▀`/`▄`, two pixels per cell stacked vertically.
+    HalfBlock,
+    /// Braille patterns, a 2x4 block of pixels per cell.
+    Braille,
+}
+
+impl Renderer {
+    /// Picks the most faithful renderer whose output fits in a terminal of
+    /// `cols` x `rows` cells, falling back to braille as the densest.
+    pub fn auto(cols: u16, rows: u16, width: usize, height: usize) -> Self {
+        [Renderer::DoubleBlock, Renderer::Block, Renderer::HalfBlock]
+            .into_iter()
+            .find(|renderer| {
+                let (c, r) = renderer.size_in_cells(width, height);
+                c <= cols as usize && r <= rows as usize
+            })
+            .unwrap_or(Renderer::Braille)
+    }
+
+    /// Terminal cells needed for a display of `width` x `height` pixels.
+    pub fn size_in_cells(&self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Renderer::Block => (width, height),
+            Renderer::DoubleBlock => (width * 2, height),
+            Renderer::HalfBlock => (width, height.div_ceil(2)),
+            Renderer::Braille => (width.div_ceil(2), height.div_ceil(4)),
+        }
+    }
+
+    /// Whether partly lit pixels are drawn shaded. The others pack several
+    /// pixels into a cell and can only show them on or off.
+    pub fn shades(&self) -> bool {
+        matches!(self, Renderer::Block | Renderer::DoubleBlock)
+    }
+
+    /// Terminal row holding pixel row `y`.
+    pub fn cell_row(&self, y: usize) -> usize {
+        match self {
+            Renderer::Block | Renderer::DoubleBlock => y,
+            Renderer::HalfBlock => y / 2,
+            Renderer::Braille => y / 4,
+        }
+    }
+
+    /// Renders the display into rows of terminal cells.
+    pub fn render<P: PixelSource + ?Sized>(&self, pixels: &P) -> Vec<Vec<Cell>> {
+        let (width, height) = pixels.size();
+        let px = |x: usize, y: usize| pixels.color_index(x, y);
+        let shaded = |x: usize, y: usize| Cell::shaded(px(x, y), pixels.brightness(x, y));
+        let (cols, rows) = self.size_in_cells(width, height);
+        (0..rows)
+            .map(|row| match self {
+                Renderer::Block => (0..cols).map(|x| shaded(x, row)).collect(),
+                Renderer::DoubleBlock => (0..cols).map(|x| shaded(x / 2, row)).collect(),
+                Renderer::HalfBlock => (0..cols)
+                    .map(|x| match (px(x, row * 2), px(x, row * 2 + 1)) {
+                        (top, bottom) if top == bottom => Cell::block(top),
+                        (top, 0) => Cell::new('▀', top, 0),
+                        (0, bottom) => Cell::new('▄', bottom, 0),
+                        (top, bottom) => Cell::new('▀', top, bottom),
+                    })
+                    .collect(),
+                Renderer::Braille => (0..cols)
+                    .map(|col| {
+                        let (x, y) = (col * 2, row * 4);
+                        let (dots, fg) = BRAILLE_DOTS
+                            .iter()
+                            .map(|(dx, dy, bit)| (px(x + dx, y + dy), bit))
+                            .filter(|(value, _)| *value != 0)
+                            .fold((0, 0), |(bits, fg), (value, bit)| {
+                                (bits | bit, u8::max(fg, value))
+                            });
+                        Cell::new(char::from_u32(0x2800 + dots).unwrap_or(' '), fg, 0)
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// A terminal character cell, colours given as palette indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: u8,
+    pub bg: u8,
+}
+
+impl Cell {
+    pub fn new(ch: char, fg: u8, bg: u8) -> Self {
+        Self { ch, fg, bg }
+    }
+
+    fn block(value: u8) -> Self {
+        Self::shaded(value, u8::MAX)
+    }
+
+    /// A full cell, using the shade characters for partly lit pixels.
+    fn shaded(value: u8, brightness: u8) -> Self {
+        let ch = match brightness {
+            _ if value == 0 => return Self::new(' ', 0, 0),
+            224.. => '█',
+            160.. => '▓',
+            96.. => '▒',
+            _ => '░',
+        };
+        Self::new(ch, value, 0)
+    }
+}
+
+/// Offset within a 2x4 braille cell and the dot bit it sets.
+pub const BRAILLE_DOTS: [(usize, usize, u32); 8] = [
+    (0, 0, 0x01),
+    (0, 1, 0x02),
+    (0, 2, 0x04),
+    (1, 0, 0x08),
+    (1, 1, 0x10),
+    (1, 2, 0x20),
+    (0, 3, 0x40),
+    (1, 3, 0x80),
+];
+
+impl std::str::FromStr for Renderer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "block" => Ok(Renderer::Block),
+            "double" => Ok(Renderer::DoubleBlock),
+            "half" => Ok(Renderer::HalfBlock),
+            "braille" => Ok(Renderer::Braille),
+            _ => Err(Error::Unknown(format!("unknown renderer: {s}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::DisplayBuffer;
+
+    fn display_with(pixels: &[(usize, usize)]) -> DisplayBuffer {
+        let mut display = DisplayBuffer::default();
+        for &(x, y) in pixels {
+            display.pixels[y][x] = 1;
+        }
+        display
+    }
+
+    #[test]
+    fn test_half_block() {
+        let display = display_with(&[(0, 0), (1, 1), (2, 0), (2, 1)]);
+        let rows = Renderer::HalfBlock.render(&display);
+        assert_eq!(rows.len(), DisplayBuffer::HEIGHT / 2);
+        let chars = rows[0][..4].iter().map(|cell| cell.ch).collect::<Vec<_>>();
+        assert_eq!(chars, ['▀', '▄', '█', ' ']);
+    }
+
+    #[test]
+    fn test_braille() {
+        let display = display_with(&[(0, 0), (1, 3)]);
+        let rows = Renderer::Braille.render(&display);
+        assert_eq!(rows.len(), DisplayBuffer::HEIGHT / 4);
+        assert_eq!(rows[0].len(), DisplayBuffer::WIDTH / 2);
+        assert_eq!(rows[0][0], Cell::new('\u{2881}', 1, 0));
+        assert_eq!(rows[0][1], Cell::new('\u{2800}', 0, 0));
+    }
+
+    #[test]
+    fn test_auto() {
+        let (w, h) = (DisplayBuffer::WIDTH, DisplayBuffer::HEIGHT);
+        assert_eq!(Renderer::auto(200, 50, w, h), Renderer::DoubleBlock);
+        assert_eq!(Renderer::auto(80, 40, w, h), Renderer::Block);
+        assert_eq!(Renderer::auto(80, 20, w, h), Renderer::HalfBlock);
+        assert_eq!(Renderer::auto(40, 10, w, h), Renderer::Braille);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::error::{Error, Result};
+
+/// Source of the bytes consumed by `CXNN`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rng {
+    Xorshift(u64),
+    /// The COSMAC VIP interpreter's routine. `r9` is the 1802 register it
+    /// keeps its state in, which the 60 Hz interrupt also bumps, so the values
+    /// depend on timing as well as the seed.
+    Vip {
+        r9: u16,
+    },
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RngKind {
+    #[default]
+    Xorshift,
+    Vip,
+}
+
+/// Page 1 of the VIP's CHIP-8 interpreter. `CXNN` runs from this page and
+/// adds one of its bytes, picked by the low half of `R9`, to the high half.
+#[rustfmt::skip]
+const VIP_PAGE: [u8; 256] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x45, 0xA3, 0x98, 0x56, 0xD4, 0xF8, 0x81, 0xBC, 0xF8, 0x95, 0xAC,
+    0x22, 0xDC, 0x12, 0x56, 0xD4, 0x06, 0xB8, 0xD4, 0x06, 0xA8, 0xD4, 0x64, 0x0A, 0x01, 0xE6, 0x8A,
+    0xF4, 0xAA, 0x3B, 0x28, 0x9A, 0xFC, 0x01, 0xBA, 0xD4, 0xF8, 0x81, 0xBA, 0x06, 0xFA, 0x0F, 0xAA,
+    0x0A, 0xAA, 0xD4, 0xE6, 0x06, 0xBF, 0x93, 0xBE, 0xF8, 0x1B, 0xAE, 0x2A, 0x1A, 0xF8, 0x00, 0x5A,
+    0x0E, 0xF5, 0x3B, 0x4B, 0x56, 0x0A, 0xFC, 0x01, 0x5A, 0x30, 0x40, 0x4E, 0xF6, 0x3B, 0x3C, 0x9F,
+    0x56, 0x2A, 0x2A, 0xD4, 0x00, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x07, 0x5A, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x5B, 0x12, 0xD4, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x0A, 0x57, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x6B, 0x12, 0xD4, 0x15, 0x85, 0x22, 0x73, 0x95, 0x52, 0x25, 0x45, 0xA5, 0x86, 0xFA,
+    0x0F, 0xB5, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x82, 0x15, 0x15, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x88,
+    0xD4, 0x45, 0x07, 0x30, 0x8C, 0x45, 0x07, 0x30, 0x84, 0xE6, 0x62, 0x26, 0x45, 0xA3, 0x36, 0x88,
+    0xD4, 0x3E, 0x88, 0xD4, 0xF8, 0xF0, 0xA7, 0xE7, 0x45, 0xF4, 0xA5, 0x86, 0xFA, 0x0F, 0x3B, 0xB2,
+    0xFC, 0x01, 0xB5, 0xD4, 0x45, 0x56, 0xD4, 0x45, 0xE6, 0xF4, 0x56, 0xD4, 0x45, 0xFA, 0x0F, 0x3A,
+    0xC4, 0x07, 0x56, 0xD4, 0xAF, 0x22, 0xF8, 0xD3, 0x73, 0x8F, 0xF9, 0xF0, 0x52, 0xE6, 0x07, 0xD2,
+    0x56, 0xF8, 0xFF, 0xA6, 0xF8, 0x00, 0x7E, 0x56, 0xD4, 0x19, 0x89, 0xAE, 0x93, 0xBE, 0x99, 0xEE,
+    0xF4, 0x56, 0x76, 0xE6, 0xF4, 0xB9, 0x56, 0x45, 0xF2, 0x56, 0xD4, 0x45, 0xAA, 0x86, 0xFA, 0x0F,
+    0xBA, 0xD4, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(RngKind::default(), rand::random::<u64>())
+    }
+}
+
+impl Rng {
+    pub fn new(kind: RngKind, seed: u64) -> Self {
+        match kind {
+            // xorshift gets stuck on a zero state, so nudge it away from it
+            RngKind::Xorshift => Rng::Xorshift(seed.max(1)),
+            RngKind::Vip => Rng::Vip { r9: seed as u16 },
+        }
+    }
+
+    pub fn next_byte(&mut self) -> u8 {
+        match self {
+            Rng::Xorshift(state) => {
+                *state ^= *state >> 12;
+                *state ^= *state << 25;
+                *state ^= *state >> 27;
+                (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+            }
+            Rng::Vip { r9 } => {
+                // INC R9, then R9.1 + M(0x100 + R9.0), rotated right through
+                // the carry and added to itself again
+                *r9 = r9.wrapping_add(1);
+                let [hi, lo] = r9.to_be_bytes();
+                let (sum, carry) = hi.overflowing_add(VIP_PAGE[lo as usize]);
+                let value = (sum >> 1 | (carry as u8) << 7).wrapping_add(sum);
+                *r9 = u16::from_be_bytes([value, lo]);
+                value
+            }
+        }
+    }
+
+    /// A copy with the per-frame counter cleared, for comparing states that
+    /// differ only in how many frames went by.
+    pub fn without_frame_counter(&self) -> Self {
+        match self {
+            Rng::Vip { .. } => Rng::Vip { r9: 0 },
+            rng => rng.clone(),
+        }
+    }
+
+    /// Called once per 60 Hz frame.
+    pub fn tick(&mut self) {
+        if let Rng::Vip { r9 } = self {
+            *r9 = r9.wrapping_add(1);
+        }
+    }
+
+    /// The kind and state as bytes, for save states.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Rng::Xorshift(state) => [&[0][..], &state.to_be_bytes()].concat(),
+            Rng::Vip { r9 } => [&[1][..], &r9.to_be_bytes()].concat(),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes {
+            [0, state @ ..] if let Ok(state) = state.try_into() => {
+                Ok(Rng::Xorshift(u64::from_be_bytes(state)))
+            }
+            [1, r9 @ ..] if let Ok(r9) = r9.try_into() => Ok(Rng::Vip {
+                r9: u16::from_be_bytes(r9),
+            }),
+            _ => Err(Error::Unknown("bad random generator state".to_string())),
+        }
+    }
+}
+
+impl std::str::FromStr for RngKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "xorshift" => Ok(RngKind::Xorshift),
+            "vip" => Ok(RngKind::Vip),
+            _ => Err(Error::Unknown(format!("unknown random generator: {s}"))),
+        }
+    }
+}
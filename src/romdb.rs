@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::{
+    config,
+    sha1::sha1_hex,
+    toml::{self, Value},
+};
+
+const DATABASE: &str = include_str!("romdb.toml");
+
+/// What the bundled database knows about a ROM.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub author: Option<String>,
+    pub year: Option<i64>,
+    /// Recommended settings as `--flag value` pairs, applied before the
+    /// config file and command line.
+    pub flags: Vec<(String, String)>,
+}
+
+/// Looks a ROM up by the SHA-1 of the whole file.
+pub fn lookup(rom: &[u8]) -> Option<RomInfo> {
+    database().get(&sha1_hex(rom)).cloned()
+}
+
+/// The bundled database by hash, parsed on first use.
+fn database() -> &'static HashMap<String, RomInfo> {
+    static PARSED: OnceLock<HashMap<String, RomInfo>> = OnceLock::new();
+    PARSED.get_or_init(|| {
+        let mut roms = HashMap::<String, RomInfo>::new();
+        for entry in toml::parse(DATABASE).expect("bundled ROM database parses") {
+            let [rom, hash] = entry.table.as_slice() else {
+                continue;
+            };
+            if rom != "rom" {
+                continue;
+            }
+            let info = roms.entry(hash.to_lowercase()).or_default();
+            match (entry.key.as_str(), &entry.value) {
+                ("title", value) => info.title = value.to_string(),
+                ("author", value) => info.author = Some(value.to_string()),
+                ("year", Value::Integer(year)) => info.year = Some(*year),
+                _ => info.flags.extend(config::flag(&entry).ok().flatten()),
+            }
+        }
+        roms
+    })
+}
+
+/// The program inside a ROM file. HP48 binaries of SCHIP games start with a
+/// 13 byte calculator header that isn't part of the program.
+pub fn program(rom: &[u8]) -> &[u8] {
+    const HP48_HEADER: usize = 13;
+    match rom.starts_with(b"HPHP48-") && rom.len() >= HP48_HEADER {
+        true => &rom[HP48_HEADER..],
+        false => rom,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_is_valid() {
+        // Every setting must be one the config file accepts
+        let settings = DATABASE
+            .lines()
+            .filter(|line| {
+                !["title", "author", "year"]
+                    .iter()
+                    .any(|k| line.starts_with(k))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        crate::config::Config::parse(&settings).unwrap();
+        assert!(lookup(&std::fs::read("roms/ibm.ch8").unwrap()).is_some());
+    }
+
+    #[test]
+    fn test_database_keys_are_hashes() {
+        for entry in toml::parse(DATABASE).unwrap() {
+            let hash = &entry.table[1];
+            assert_eq!(entry.table[0], "rom");
+            assert!(
+                hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()),
+                "bad hash {hash}"
+            );
+        }
+        assert_eq!(database().len(), DATABASE.matches("[rom.").count());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let rom = std::fs::read("roms/br8kout.ch8").unwrap();
+        let info = lookup(&rom).unwrap();
+        assert_eq!(info.title, "Br8kout");
+        assert_eq!(
+            info.flags,
+            [("--platform".to_string(), "chip8".to_string())]
+        );
+        assert_eq!(lookup(&[0x12, 0x00]), None);
+    }
+
+    #[test]
+    fn test_hp48_header() {
+        let rom = std::fs::read("roms/TETRIS.BIN").unwrap();
+        assert_eq!(&program(&rom)[..2], [0x12, 0x1E]);
+        assert_eq!(program(&[0x00, 0xE0]), [0x00, 0xE0]);
+    }
+}
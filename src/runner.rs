@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+use std::io::Write;
+
+use crate::{
+    cheats::Cheats,
+    chip8::{Chip8, CpuState},
+    debugger::Debugger,
+    disasm::listing_line,
+    display::DisplayBuffer,
+    error::Result,
+    history::History,
+    input::Keypad,
+    memory::Memory,
+    profiler::Profiler,
+};
+
+type Pixels = [[u8; DisplayBuffer::WIDTH]; DisplayBuffer::HEIGHT];
+
+/// How a headless run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// Ran every frame asked for.
+    Completed,
+    /// Stopped after `frames` frames at a jump to itself or a loop that
+    /// changes nothing.
+    Halted { pc: u16, frames: u64 },
+}
+
+/// Drives the machine in 60 Hz frames of `ipf` instructions each, with
+/// optional cheats, instruction tracing, profiling and history. Shared by the
+/// terminal and headless modes.
+pub struct Runner {
+    pub chip: Chip8,
+    ipf: usize,
+    trace: Option<Box<dyn Write>>,
+    profiler: Option<Profiler>,
+    history: Option<History>,
+    /// Whether a `CXNN` ran since halt detection last looked.
+    read_rng: bool,
+    cheats: Cheats,
+}
+
+impl Runner {
+    /// Instructions per frame when nothing else is configured, roughly the
+    /// speed most CHIP-8 games were tuned for.
+    pub const DEFAULT_IPF: usize = 15;
+
+    pub fn new(chip: Chip8, ipf: usize) -> Self {
+        Self {
+            chip,
+            ipf: ipf.max(1),
+            trace: None,
+            profiler: None,
+            history: None,
+            read_rng: false,
+            cheats: Cheats::default(),
+        }
+    }
+
+    /// Logs every executed instruction to `out`.
+    pub fn with_trace(mut self, out: Box<dyn Write>) -> Self {
+        self.trace = Some(out);
+        self
+    }
+
+    /// Counts executed instructions, read back through `profiler`.
+    pub fn with_profiler(mut self) -> Self {
+        self.profiler = Some(Profiler::default());
+        self
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Holds the values frozen by `cheats` at the start of every frame.
+    pub fn with_cheats(mut self, cheats: Cheats) -> Self {
+        self.cheats = cheats;
+        self
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        &mut self.cheats
+    }
+
+    /// Starts recording undo information for every instruction so
+    /// `step_back` can run the machine backwards. Does nothing once started.
+    pub fn start_history(&mut self) {
+        if self.history.is_none() {
+            self.history = Some(History::default());
+            self.chip.set_access_log(true);
+        }
+    }
+
+    /// Logs memory accesses while watchpoints need them, or history does.
+    pub fn log_accesses(&mut self, watching: bool) {
+        self.chip.set_access_log(watching || self.history.is_some());
+    }
+
+    /// Undoes the last instruction, or a whole snapshot interval once the
+    /// fine history runs out. Returns how many instructions went back, `None`
+    /// without history or at its start.
+    pub fn step_back(&mut self) -> Option<usize> {
+        self.history.as_mut()?.step_back(&mut self.chip)
+    }
+
+    /// Restores a save state. History from before it would undo into a
+    /// different machine, so it starts over.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<()> {
+        self.chip.load_state(data)?;
+        if self.history.is_some() {
+            self.history = Some(History::default());
+        }
+        Ok(())
+    }
+
+    /// Executes a single instruction.
+    pub fn step(&mut self, keypad: &Keypad) -> Result<()> {
+        let pc = self.chip.pc();
+        let opcode = self.chip.next_opcode()?;
+        if let Some(trace) = &mut self.trace {
+            writeln!(trace, "{}", listing_line(pc, opcode))?;
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(pc, opcode, self.chip.call_stack());
+        }
+        self.read_rng |= opcode.code() == 0xC;
+        match &mut self.history {
+            Some(history) => history.record(&mut self.chip, |chip| chip.cycle(keypad)),
+            None => self.chip.cycle(keypad),
+        }
+    }
+
+    /// Runs one frame: up to `ipf` instructions, then the 60 Hz tick. Returns
+    /// early, without ticking, when the debugger pauses.
+    pub fn frame(&mut self, keypad: &Keypad, debugger: &mut Debugger) -> Result<()> {
+        self.cheats.apply(&mut self.chip);
+        for _ in 0..self.ipf {
+            if debugger.check(&self.chip) {
+                return Ok(());
+            }
+            self.step(keypad)?;
+        }
+        self.chip.tick();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end_frame();
+        }
+        Ok(())
+    }
+
+    /// Runs up to `frames` frames with no input attached, stopping early
+    /// once the program halts.
+    pub fn run_headless(&mut self, frames: u64) -> Result<RunOutcome> {
+        // With a loop of n instructions, the state at the end of a frame
+        // comes round again within n frames, so this catches any loop of up
+        // to this many instructions
+        const RECENT_STATES: usize = 64;
+        let keypad = Keypad::default();
+        let mut debugger = Debugger::default();
+        let mut recent = VecDeque::with_capacity(RECENT_STATES);
+        for frame in 1..=frames {
+            self.frame(&keypad, &mut debugger)?;
+            // States are compared without the RNG's frame counter, which is
+            // only safe if nothing read the RNG in between
+            if std::mem::take(&mut self.read_rng) {
+                recent.clear();
+            }
+            let state = self.halt_state();
+            if self.at_self_jump() || recent.contains(&state) {
+                self.finish()?;
+                return Ok(RunOutcome::Halted {
+                    pc: self.chip.pc(),
+                    frames: frame,
+                });
+            }
+            if recent.len() == RECENT_STATES {
+                recent.pop_front();
+            }
+            recent.push_back(state);
+        }
+        self.finish()?;
+        Ok(RunOutcome::Completed)
+    }
+
+    /// Whether the next instruction is a `1NNN` jumping to itself, the usual
+    /// way a program ends.
+    fn at_self_jump(&self) -> bool {
+        self.chip
+            .next_opcode()
+            .is_ok_and(|opcode| opcode.code() == 0x1 && opcode.nnn() == self.chip.pc())
+    }
+
+    /// Everything execution depends on. With no input, reaching a state seen
+    /// before means the program is stuck in a loop for good.
+    fn halt_state(&self) -> (CpuState, Vec<u8>, Pixels) {
+        let memory = self
+            .chip
+            .read_memory(0, Memory::MEMORY_SIZE)
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default();
+        (
+            self.chip.loop_state(),
+            memory,
+            self.chip.display_buffer.pixels,
+        )
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        if let Some(trace) = &mut self.trace {
+            trace.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::{Rng, RngKind};
+
+    fn run(rom: &[u8], frames: u64) -> RunOutcome {
+        run_with(Rng::default(), rom, frames)
+    }
+
+    fn run_with(rng: Rng, rom: &[u8], frames: u64) -> RunOutcome {
+        let mut chip = Chip8::default().with_rng(rng);
+        chip.load_rom(rom).unwrap();
+        Runner::new(chip, 10).run_headless(frames).unwrap()
+    }
+
+    #[test]
+    fn test_halt_detection() {
+        // 0x200: CLS; 0x202: JP 0x202
+        assert_eq!(
+            run(&[0x00, 0xE0, 0x12, 0x02], 100),
+            RunOutcome::Halted {
+                pc: 0x202,
+                frames: 1
+            }
+        );
+        // 0x200: LD V0, 0x05; 0x202: SE V0, 0x05; 0x204: JP 0x202;
+        // 0x206: JP 0x202
+        assert_eq!(
+            run(&[0x60, 0x05, 0x30, 0x05, 0x12, 0x02, 0x12, 0x02], 100),
+            RunOutcome::Halted {
+                pc: 0x206,
+                frames: 2
+            }
+        );
+        // 0x200: LD V0, 0x05; 0x202: LD V1, 0x05; 0x204: LD V2, 0x05;
+        // 0x206: JP 0x202, a loop of 3 with 10 instructions per frame
+        assert!(matches!(
+            run(&[0x60, 0x05, 0x61, 0x05, 0x62, 0x05, 0x12, 0x02], 100),
+            RunOutcome::Halted { frames: 4, .. }
+        ));
+        // 0x200: ADD V0, 0x01; 0x202: JP 0x200
+        assert_eq!(run(&[0x70, 0x01, 0x12, 0x00], 100), RunOutcome::Completed);
+    }
+
+    #[test]
+    fn test_halt_detection_ignores_frame_counter() {
+        // 0x200: LD V0, 0x05; 0x202: SE V0, 0x05; 0x204: JP 0x202;
+        // 0x206: JP 0x202
+        let rom = [0x60, 0x05, 0x30, 0x05, 0x12, 0x02, 0x12, 0x02];
+        let rng = Rng::new(RngKind::Vip, 1234);
+        assert!(matches!(
+            run_with(rng.clone(), &rom, 100),
+            RunOutcome::Halted { frames: 2, .. }
+        ));
+        // 0x200: RND V0, 0xFF; 0x202: JP 0x200 keeps changing V0
+        assert_eq!(
+            run_with(rng, &[0xC0, 0xFF, 0x12, 0x00], 100),
+            RunOutcome::Completed
+        );
+    }
+}
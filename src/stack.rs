@@ -1,6 +1,6 @@
 use std::collections::VecDeque;
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Stack(VecDeque<u16>);
 
 impl Stack {
@@ -11,4 +11,14 @@ impl Stack {
     pub fn push(&mut self, value: u16) {
         self.0.push_back(value);
     }
+
+    /// Drops the innermost frames, or pushes zeros, until `depth` remain.
+    pub fn resize(&mut self, depth: usize) {
+        self.0.resize(depth, 0);
+    }
+
+    /// Return addresses, outermost first.
+    pub fn frames(&self) -> impl Iterator<Item = u16> + '_ {
+        self.0.iter().copied()
+    }
 }
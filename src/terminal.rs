@@ -0,0 +1,217 @@
+use std::io::{self, Write};
+
+use crossterm::{cursor, queue, style};
+
+use crate::{
+    display::{DisplayBuffer, PixelSource},
+    flicker::{FlickerFilter, FlickerMode},
+    graphics::GraphicsProtocol,
+    palette::{ColorMode, Palette},
+    renderer::{Cell, Renderer},
+};
+
+/// Draws the display into the terminal, only emitting the cells that changed
+/// since the previous frame, or as a bitmap when a graphics protocol is set.
+#[derive(Debug)]
+pub struct TerminalFrontend {
+    renderer: Renderer,
+    palette: Option<(Palette, ColorMode)>,
+    flicker: Option<FlickerFilter>,
+    graphics: Option<(GraphicsProtocol, usize)>,
+    previous: Option<Vec<Vec<Cell>>>,
+}
+
+impl TerminalFrontend {
+    pub fn new(renderer: Renderer) -> Self {
+        Self {
+            renderer,
+            palette: None,
+            flicker: None,
+            graphics: None,
+            previous: None,
+        }
+    }
+
+    /// Colours pixels from `palette` instead of the terminal's default colours.
+    pub fn with_palette(mut self, palette: Palette, mode: ColorMode) -> Self {
+        self.palette = Some((palette, mode));
+        self
+    }
+
+    pub fn with_flicker(mut self, mode: FlickerMode) -> Self {
+        self.flicker = Some(FlickerFilter::new(mode));
+        self
+    }
+
+    /// Draws frames as images scaled up by `scale` instead of character cells.
+    pub fn with_graphics(mut self, protocol: GraphicsProtocol, scale: usize) -> Self {
+        self.graphics = Some((protocol, scale));
+        self
+    }
+
+    /// Forgets what is on screen so the next frame is drawn in full.
+    pub fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    pub fn render_to_screen<W: Write>(
+        &mut self,
+        out: &mut W,
+        display_buffer: &mut DisplayBuffer,
+    ) -> Result<(), io::Error> {
+        let dirty = display_buffer.take_dirty();
+        let draw = match &mut self.flicker {
+            Some(filter) => {
+                let settled = filter.settled();
+                filter.push(display_buffer);
+                dirty.is_some() || !settled
+            }
+            None => dirty.is_some(),
+        };
+        if !draw {
+            return Ok(());
+        }
+        let pixels: &dyn PixelSource = match &self.flicker {
+            Some(filter) => filter.frame(),
+            None => display_buffer,
+        };
+
+        if let Some((protocol, scale)) = self.graphics {
+            let palette = self
+                .palette
+                .map_or(Palette::CLASSIC, |(palette, _)| palette);
+            let image = protocol.encode(pixels, scale, &palette);
+            queue!(out, cursor::MoveTo(0, 0), style::Print(image))?;
+            return out.flush();
+        }
+
+        let rows = self.renderer.render(pixels);
+        let (first_row, last_row) = match (&self.flicker, dirty) {
+            (None, Some(dirty)) => (
+                self.renderer.cell_row(dirty.y0),
+                self.renderer.cell_row(dirty.y1),
+            ),
+            _ => (0, usize::MAX),
+        };
+        for (y, row) in rows.iter().enumerate() {
+            let previous = match &self.previous {
+                Some(_) if !(first_row..=last_row).contains(&y) => continue,
+                Some(previous) => previous.get(y),
+                None => None,
+            };
+            let mut x = 0;
+            while x < row.len() {
+                let changed = |x: usize| previous.and_then(|p| p.get(x)) != Some(&row[x]);
+                if !changed(x) {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < row.len() && changed(x) {
+                    x += 1;
+                }
+                queue!(out, cursor::MoveTo(start as u16, y as u16))?;
+                self.print_cells(out, &row[start..x])?;
+            }
+        }
+        if self.palette.is_some() {
+            queue!(out, style::ResetColor)?;
+        }
+        out.flush()?;
+        self.previous = Some(rows);
+        Ok(())
+    }
+
+    fn print_cells<W: Write>(&self, out: &mut W, cells: &[Cell]) -> Result<(), io::Error> {
+        let Some((palette, mode)) = self.palette else {
+            return queue!(
+                out,
+                style::Print(cells.iter().map(|cell| cell.ch).collect::<String>())
+            );
+        };
+        let mut start = 0;
+        for (i, cell) in cells.iter().enumerate() {
+            if i + 1 < cells.len() && (cells[i + 1].fg, cells[i + 1].bg) == (cell.fg, cell.bg) {
+                continue;
+            }
+            queue!(
+                out,
+                style::SetForegroundColor(mode.color(palette.color(cell.fg))),
+                style::SetBackgroundColor(mode.color(palette.color(cell.bg))),
+                style::Print(
+                    cells[start..=i]
+                        .iter()
+                        .map(|cell| cell.ch)
+                        .collect::<String>()
+                )
+            )?;
+            start = i + 1;
+        }
+        Ok(())
+    }
+}
+
+/// Draws `lines` at the bottom of the terminal above a prompt, leaving the
+/// cursor after the prompt.
+pub fn draw_panel<W: Write>(out: &mut W, lines: &[String], prompt: &str) -> io::Result<()> {
+    let (_, rows) = crossterm::terminal::size()?;
+    let top = rows.saturating_sub(lines.len() as u16 + 1);
+    queue!(
+        out,
+        cursor::MoveTo(0, top),
+        crossterm::terminal::Clear(crossterm::terminal::ClearType::FromCursorDown)
+    )?;
+    for (i, line) in lines.iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, top + i as u16), style::Print(line))?;
+    }
+    queue!(
+        out,
+        cursor::MoveTo(0, rows.saturating_sub(1)),
+        style::Print(prompt)
+    )?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_only_changes() {
+        let mut display = DisplayBuffer::default();
+        let mut frontend = TerminalFrontend::new(Renderer::Block);
+        let mut out = Vec::new();
+        frontend.render_to_screen(&mut out, &mut display).unwrap();
+        assert!(!out.is_empty());
+
+        out.clear();
+        frontend.render_to_screen(&mut out, &mut display).unwrap();
+        assert!(out.is_empty());
+
+        display.set(3usize, 2, 1).unwrap();
+        display.set(4usize, 2, 1).unwrap();
+        frontend.render_to_screen(&mut out, &mut display).unwrap();
+        let mut expected = Vec::new();
+        queue!(
+            expected,
+            cursor::MoveTo(3, 2),
+            style::Print("██".to_string())
+        )
+        .unwrap();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_render_with_palette() {
+        let mut display = DisplayBuffer::default();
+        display.set(0usize, 0, 1).unwrap();
+        let mut frontend = TerminalFrontend::new(Renderer::Block)
+            .with_palette(Palette::AMBER, ColorMode::TrueColor);
+        let mut out = Vec::new();
+        frontend.render_to_screen(&mut out, &mut display).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with("\x1b[1;1H\x1b[38;2;255;176;0m\x1b[48;2;26;16;0m█\x1b[38;2;26;16;0m")
+        );
+    }
+}
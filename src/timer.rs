@@ -2,4 +2,8 @@ use crate::new_register;
 
 new_register!(Timer, u8);
 
-impl Timer {}
+impl Timer {
+    pub fn decrement(&mut self) {
+        self.0 = self.0.saturating_sub(1);
+    }
+}
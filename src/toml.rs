@@ -0,0 +1,225 @@
+use std::fmt;
+
+use crate::error::{Error, Result};
+
+/// A scalar TOML value. Arrays and inline tables aren't supported.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{s}"),
+            Value::Integer(i) => write!(f, "{i}"),
+            Value::Float(x) => write!(f, "{x}"),
+            Value::Boolean(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+/// One `key = value` line with the path of the table it appeared under.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub table: Vec<String>,
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+/// Parses the subset of TOML used by the config and ROM database files:
+/// `[table."quoted key"]` headers, bare or quoted keys and scalar values.
+pub fn parse(text: &str) -> Result<Vec<Entry>> {
+    let mut table = Vec::new();
+    let mut entries = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let line = i + 1;
+        let at_line = |e: Error| Error::Unknown(format!("line {line}: {e}"));
+        let content = strip_comment(raw).trim();
+        if content.is_empty() {
+            continue;
+        }
+        if let Some(header) = content.strip_prefix('[') {
+            let header = header
+                .strip_suffix(']')
+                .ok_or_else(|| at_line(Error::Unknown("unclosed table header".to_string())))?;
+            table = parse_key(header).map_err(at_line)?;
+            continue;
+        }
+        let (key, value) = content
+            .split_once('=')
+            .ok_or_else(|| at_line(Error::Unknown(format!("expected key = value: {content}"))))?;
+        let mut key = parse_key(key).map_err(at_line)?;
+        let value = parse_value(value.trim()).map_err(at_line)?;
+        let name = key.pop().unwrap_or_default();
+        entries.push(Entry {
+            table: table.iter().chain(&key).cloned().collect(),
+            key: name,
+            value,
+            line,
+        });
+    }
+    Ok(entries)
+}
+
+/// The table a `[header]` line opens, `None` for any other line.
+pub fn header(line: &str) -> Option<Vec<String>> {
+    let header = strip_comment(line).trim().strip_prefix('[')?;
+    parse_key(header.strip_suffix(']')?).ok()
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            _ if escaped => escaped = false,
+            ('\\', Some('"')) => escaped = true,
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits a dotted key into its parts, unquoting quoted parts.
+fn parse_key(key: &str) -> Result<Vec<String>> {
+    let mut parts = Vec::new();
+    let mut rest = key.trim();
+    while !rest.is_empty() {
+        let (part, after) = match rest.chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let end = rest[1..]
+                    .find(q)
+                    .ok_or_else(|| Error::Unknown(format!("unclosed quote in key: {key}")))?;
+                (rest[1..end + 1].to_string(), &rest[end + 2..])
+            }
+            _ => {
+                let end = rest.find('.').unwrap_or(rest.len());
+                let part = rest[..end].trim();
+                let bare = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+                if part.is_empty() || !part.chars().all(bare) {
+                    return Err(Error::Unknown(format!("bad key: {key}")));
+                }
+                (part.to_string(), &rest[end..])
+            }
+        };
+        parts.push(part);
+        rest = after.trim_start();
+        rest = match rest.strip_prefix('.') {
+            Some(next) => next.trim_start(),
+            None if rest.is_empty() => rest,
+            None => return Err(Error::Unknown(format!("bad key: {key}"))),
+        };
+    }
+    if parts.is_empty() {
+        return Err(Error::Unknown("empty key".to_string()));
+    }
+    Ok(parts)
+}
+
+fn parse_value(value: &str) -> Result<Value> {
+    if let Some(literal) = value.strip_prefix('\'') {
+        return literal
+            .strip_suffix('\'')
+            .map(|s| Value::String(s.to_string()))
+            .ok_or_else(|| Error::Unknown(format!("unclosed string: {value}")));
+    }
+    if let Some(basic) = value.strip_prefix('"') {
+        let basic = basic
+            .strip_suffix('"')
+            .ok_or_else(|| Error::Unknown(format!("unclosed string: {value}")))?;
+        let mut out = String::with_capacity(basic.len());
+        let mut chars = basic.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            out.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('"') => '"',
+                Some('\\') => '\\',
+                other => {
+                    return Err(Error::Unknown(format!("unsupported escape: \\{other:?}")));
+                }
+            });
+        }
+        return Ok(Value::String(out));
+    }
+    match value {
+        "true" => return Ok(Value::Boolean(true)),
+        "false" => return Ok(Value::Boolean(false)),
+        _ => {}
+    }
+    let digits = value.replace('_', "");
+    let integer = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => digits.parse().ok(),
+    };
+    integer
+        .map(Value::Integer)
+        .or_else(|| digits.parse().ok().map(Value::Float))
+        .ok_or_else(|| Error::Unknown(format!("unsupported value: {value}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = r##"
+            # defaults
+            ipf = 20
+            palette = "amber"   # trailing comment
+            volume = 0.5
+
+            [rom."0123abcd"]
+            platform = 'xochip'
+            display_wait = false
+            name = "say \"#hi\" # not a comment"
+        "##;
+        let entries = parse(text).unwrap();
+        let summary = entries
+            .iter()
+            .map(|e| (e.table.join("/"), e.key.as_str(), e.value.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (String::new(), "ipf", Value::Integer(20)),
+                (String::new(), "palette", Value::String("amber".into())),
+                (String::new(), "volume", Value::Float(0.5)),
+                (
+                    "rom/0123abcd".into(),
+                    "platform",
+                    Value::String("xochip".into())
+                ),
+                ("rom/0123abcd".into(), "display_wait", Value::Boolean(false)),
+                (
+                    "rom/0123abcd".into(),
+                    "name",
+                    Value::String("say \"#hi\" # not a comment".into())
+                ),
+            ]
+        );
+        assert_eq!(entries[3].line, 8);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("[rom").is_err());
+        assert!(parse("key").is_err());
+        assert!(parse("key = [1, 2]").is_err());
+        assert!(parse("bad key = 1").is_err());
+        assert!(parse("k = \"open").is_err());
+    }
+}
@@ -9,12 +9,12 @@ pub fn debug_out<T: Debug>(msg: T) {
     execute!(
         stdout,
         MoveTo(
-            (Chip8Display::WIDTH + 5) as u16,
+            (Chip8Display::WIDTH * 2 + 5) as u16,
             (Chip8Display::HEIGHT / 2) as u16
         ),
         Print("                       "),
         MoveTo(
-            (Chip8Display::WIDTH + 5) as u16,
+            (Chip8Display::WIDTH * 2 + 5) as u16,
             (Chip8Display::HEIGHT / 2) as u16
         ),
         Print(format!("{msg:?}"))